@@ -0,0 +1,314 @@
+use std::{collections::BTreeMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::dataset::{read_feature_csv, read_feature_jsonl};
+
+use super::{
+    dataset::{LoadReport, ParseMode, SkippedRecord},
+    error::DatasetError,
+    item_index::ItemIndex,
+    schema::FeatureSchema,
+};
+
+/// Side attributes of a single item or user.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Features {
+    categorical: BTreeMap<String, Vec<String>>,
+    numeric: BTreeMap<String, f64>,
+    text: BTreeMap<String, String>,
+}
+
+impl Features {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a value to a categorical attribute, e.g. one more tag.
+    pub fn with_categorical(
+        mut self,
+        attribute: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        let values = self.categorical.entry(attribute.into()).or_default();
+        let value = value.into();
+        if !values.contains(&value) {
+            values.push(value);
+        }
+        self
+    }
+
+    pub fn with_numeric(mut self, attribute: impl Into<String>, value: f64) -> Self {
+        self.numeric.insert(attribute.into(), value);
+        self
+    }
+
+    pub fn with_text(mut self, attribute: impl Into<String>, value: impl Into<String>) -> Self {
+        self.text.insert(attribute.into(), value.into());
+        self
+    }
+
+    /// Values of a categorical attribute, empty when the attribute is not set.
+    pub fn categorical(&self, attribute: &str) -> &[String] {
+        self.categorical
+            .get(attribute)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn numeric(&self, attribute: &str) -> Option<f64> {
+        self.numeric.get(attribute).copied()
+    }
+
+    pub fn text(&self, attribute: &str) -> Option<&str> {
+        self.text.get(attribute).map(String::as_str)
+    }
+
+    /// Whether the categorical attribute holds `value`.
+    pub fn has(&self, attribute: &str, value: &str) -> bool {
+        self.categorical(attribute).iter().any(|v| v == value)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.categorical.is_empty() && self.numeric.is_empty() && self.text.is_empty()
+    }
+}
+
+/// Summary of a [`FeatureStore`] load, see [`LoadReport`] for the loads of interactions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FeatureLoadReport {
+    pub records_read: usize,
+    /// Entries stored, one per id of the index found in the file.
+    pub entries_loaded: usize,
+    /// Valid records left out because their id is not in the index.
+    pub entries_ignored: usize,
+    pub skipped: Vec<SkippedRecord>,
+}
+
+impl FeatureLoadReport {
+    pub fn records_skipped(&self) -> usize {
+        self.skipped.len()
+    }
+
+    /// Turns the report of the record readers, which count kept entries as loaded events.
+    pub(crate) fn from_records(report: LoadReport) -> Self {
+        Self {
+            records_read: report.records_read,
+            entries_loaded: report.events_loaded,
+            entries_ignored: report.records_read - report.events_loaded - report.skipped.len(),
+            skipped: report.skipped,
+        }
+    }
+}
+
+/// Attributes of the entries of an [`ItemIndex`], stored by index so that they line up with the
+/// rows and columns of the [`Dataset`](super::dataset::Dataset) the index belongs to.
+///
+/// Entries without attributes have empty [`Features`].
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FeatureStore {
+    features: Vec<Features>,
+}
+
+/// Attributes of the items of a dataset, keyed by its `item_idx`.
+pub type ItemCatalog = FeatureStore;
+
+/// Attributes of the users of a dataset, keyed by its `user_idx`.
+pub type UserProfiles = FeatureStore;
+
+impl FeatureStore {
+    /// An empty store with room for every entry of `index`.
+    pub fn new(index: &ItemIndex) -> Self {
+        Self {
+            features: vec![Features::default(); index.size()],
+        }
+    }
+
+    /// Loads the attributes of a csv file with a header.
+    ///
+    /// Rows whose id is not in `index`, such as items nobody interacted with, are left out and
+    /// counted in [`FeatureLoadReport::entries_ignored`]. Later rows of the same id replace
+    /// earlier ones.
+    pub fn from_csv<P: AsRef<Path>>(
+        path: P,
+        index: &ItemIndex,
+        schema: &FeatureSchema,
+        mode: ParseMode,
+    ) -> Result<(Self, FeatureLoadReport), DatasetError> {
+        let mut store = Self::new(index);
+        let report = read_feature_csv(path.as_ref(), schema, mode, |id, features| {
+            store.insert_by_id(index, &id, features)
+        })?;
+
+        Ok((store, report))
+    }
+
+    /// Loads the attributes of a jsonl file, one object per entry, see [`from_csv`](Self::from_csv).
+    pub fn from_jsonl<P: AsRef<Path>>(
+        path: P,
+        index: &ItemIndex,
+        schema: &FeatureSchema,
+        mode: ParseMode,
+    ) -> Result<(Self, FeatureLoadReport), DatasetError> {
+        let mut store = Self::new(index);
+        let report = read_feature_jsonl(path.as_ref(), schema, mode, |id, features| {
+            store.insert_by_id(index, &id, features)
+        })?;
+
+        Ok((store, report))
+    }
+
+    /// Sets the attributes of the entry at `idx`, growing the store if the index grew since.
+    pub fn insert(&mut self, idx: usize, features: Features) {
+        if idx >= self.features.len() {
+            self.features.resize(idx + 1, Features::default());
+        }
+        self.features[idx] = features;
+    }
+
+    fn insert_by_id(&mut self, index: &ItemIndex, id: &str, features: Features) -> bool {
+        match index.lookup(id) {
+            Some(idx) => {
+                self.insert(idx, features);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Attributes of the entry at `idx`, `None` past the end of the store.
+    pub fn get(&self, idx: usize) -> Option<&Features> {
+        self.features.get(idx)
+    }
+
+    /// Number of entries the store has room for.
+    pub fn len(&self) -> usize {
+        self.features.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+
+    /// Indexes of the entries whose categorical attribute holds `value`.
+    pub fn matching<'a>(
+        &'a self,
+        attribute: &'a str,
+        value: &'a str,
+    ) -> impl Iterator<Item = usize> + 'a {
+        self.features
+            .iter()
+            .enumerate()
+            .filter(move |(_, features)| features.has(attribute, value))
+            .map(|(idx, _)| idx)
+    }
+}
+
+#[cfg(test)]
+mod catalog_test {
+    use std::{env, fs};
+
+    use crate::core::{
+        dataset::ParseMode,
+        error::DatasetError,
+        item_index::ItemIndex,
+        schema::{FeatureField, FeatureSchema},
+    };
+
+    use super::{Features, ItemCatalog};
+
+    fn schema() -> FeatureSchema {
+        FeatureSchema::new("item_id")
+            .with_categorical("category")
+            .with_categorical("tags")
+            .with_numeric("price")
+            .with_text("description")
+    }
+
+    #[test]
+    fn should_load_a_csv_catalog_by_index() {
+        let index = ["p-2", "p-1"]
+            .map(String::from)
+            .into_iter()
+            .collect::<ItemIndex>();
+        let path = env::temp_dir().join("rs_mender_catalog.csv");
+        fs::write(
+            &path,
+            "item_id,category,tags,price,description\n\
+             p-1,shoes,running|trail,89.90,Light trail shoes\n\
+             p-2,socks,,4.5,\n\
+             p-3,hats,winter,12,Not in the dataset\n",
+        )
+        .unwrap();
+
+        let (catalog, report) =
+            ItemCatalog::from_csv(&path, &index, &schema(), ParseMode::Strict).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(3, report.records_read);
+        assert_eq!(2, report.entries_loaded);
+        assert_eq!(1, report.entries_ignored);
+        assert_eq!(2, catalog.len());
+
+        let p1 = catalog.get(1).unwrap();
+        assert_eq!(["shoes"], p1.categorical("category"));
+        assert_eq!(["running", "trail"], p1.categorical("tags"));
+        assert_eq!(Some(89.9), p1.numeric("price"));
+        assert_eq!(Some("Light trail shoes"), p1.text("description"));
+
+        let p2 = catalog.get(0).unwrap();
+        assert!(p2.categorical("tags").is_empty());
+        assert_eq!(None, p2.text("description"));
+
+        assert_eq!(
+            vec![1],
+            catalog.matching("tags", "trail").collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn should_load_a_jsonl_catalog_with_nested_attributes() {
+        let index = ["p-1"].map(String::from).into_iter().collect::<ItemIndex>();
+        let path = env::temp_dir().join("rs_mender_catalog.jsonl");
+        fs::write(
+            &path,
+            r#"{"id": "p-1", "brand": {"name": "acme"}, "tags": ["a", "b"], "price": "3.5"}"#,
+        )
+        .unwrap();
+        let schema = FeatureSchema::new("/id")
+            .with_categorical("/brand/name")
+            .with_categorical(FeatureField::new("tags").named("tag"))
+            .with_numeric("price");
+
+        let (catalog, _) =
+            ItemCatalog::from_jsonl(&path, &index, &schema, ParseMode::Strict).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let expected = Features::new()
+            .with_categorical("brand.name", "acme")
+            .with_categorical("tag", "a")
+            .with_categorical("tag", "b")
+            .with_numeric("price", 3.5);
+        assert_eq!(Some(&expected), catalog.get(0));
+    }
+
+    #[test]
+    fn should_reject_invalid_numeric_attributes() {
+        let index = ["p-1"].map(String::from).into_iter().collect::<ItemIndex>();
+        let path = env::temp_dir().join("rs_mender_invalid_catalog.csv");
+        fs::write(
+            &path,
+            "item_id,category,tags,price,description\np-1,shoes,,cheap,\n",
+        )
+        .unwrap();
+
+        let strict = ItemCatalog::from_csv(&path, &index, &schema(), ParseMode::Strict);
+        let (catalog, report) =
+            ItemCatalog::from_csv(&path, &index, &schema(), ParseMode::Lenient).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(strict, Err(DatasetError::Parse { line: 2, .. })));
+        assert_eq!(1, report.records_skipped());
+        assert!(catalog.get(0).unwrap().is_empty());
+    }
+}
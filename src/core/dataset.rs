@@ -1,124 +1,1181 @@
 use std::{
-    path::Path,
-    sync::{Arc, Mutex},
+    collections::HashMap,
+    path::{Path, PathBuf},
+    thread,
 };
 
-use sprs::{CsMat, CsMatBase, TriMat};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+use serde::{Deserialize, Serialize};
+use sprs::{CsMat, TriMat};
 
 use crate::utils::dataset;
 
-use super::item_index::ItemIndex;
+pub use super::split::{CrossValidationReport, FoldStrategy, KFold};
+
+use super::{
+    error::DatasetError,
+    item_index::ItemIndex,
+    model::{Event, EventType},
+    schema::{BenchmarkFormat, CsvSchema, InputFormat, JsonlSchema},
+};
 
 pub struct Dataset {
-    pub cui: CsMat<u32>,
-    ciu: CsMat<u32>,
+    pub cui: CsMat<f64>,
+    ciu: CsMat<f64>,
     pub user_idx: ItemIndex,
     pub item_idx: ItemIndex,
+    // unix milliseconds of the latest event of each (user, item) pair, for the pairs that have one.
+    last_seen: Option<CsMat<i64>>,
+    parameters: BuildParameters,
+}
+
+/// Settings a [`Dataset`] was built with, kept along with it so that snapshots record how they
+/// were made.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BuildParameters {
+    pub event_weights: EventWeights,
+    pub time_decay: Option<TimeDecay>,
+    pub aggregation: Aggregation,
+    pub feedback: FeedbackKind,
 }
 
 impl Dataset {
-    pub fn new(cui: CsMat<u32>, ciu: CsMat<u32>, user_idx: ItemIndex, item_idx: ItemIndex) -> Self {
+    /// Freezes both indexes, so that querying the dataset with unknown ids can not make them
+    /// outgrow the matrices.
+    pub fn new(
+        cui: CsMat<f64>,
+        ciu: CsMat<f64>,
+        mut user_idx: ItemIndex,
+        mut item_idx: ItemIndex,
+    ) -> Self {
+        user_idx.freeze();
+        item_idx.freeze();
         Self {
             cui,
             ciu,
             user_idx,
             item_idx,
+            last_seen: None,
+            parameters: BuildParameters::default(),
         }
     }
 
-    pub fn from_jsonl(path: String) -> Self {
-        let mut user_idx = ItemIndex::new();
-        let mut item_idx = ItemIndex::new();
+    /// Creates a [`Dataset`] from a user-item matrix, deriving the item-user matrix from it.
+    pub fn from_cui(cui: CsMat<f64>, user_idx: ItemIndex, item_idx: ItemIndex) -> Self {
+        let cui = cui.into_csr();
+        let ciu = cui.transpose_view().to_csr();
 
-        // learn the indexes in the data. Also gives us the information about how many users and items in it.
-        // might need to find a better solution in the future because we need to go through the dataset twice in this case.
-        dataset::read_test_data(Path::new(&path), 0, |e| {
-            let events = e.to_events();
+        Self::new(cui, ciu, user_idx, item_idx)
+    }
 
-            events.iter().for_each(|e| {
-                let _user_idx = user_idx.get_idx(e.user_id().to_string());
-                let _item_idx = item_idx.get_idx(e.target_id().to_string());
-            });
-        });
+    /// Builds a [`Dataset`] from any source of [`Event`]s in a single pass.
+    pub fn from_events<I: IntoIterator<Item = Event>>(events: I) -> Self {
+        let mut builder = DatasetBuilder::new();
+        builder.extend(events);
+        builder.build()
+    }
 
-        let user_size = user_idx.size();
-        let item_size = item_idx.size();
+    /// Loads a jsonl file whose fields are described by `schema`.
+    pub fn from_jsonl<P: AsRef<Path>>(
+        path: P,
+        schema: &JsonlSchema,
+        mode: ParseMode,
+    ) -> Result<(Self, LoadReport), DatasetError> {
+        let mut builder = DatasetBuilder::new();
+        let report = builder.read_jsonl(path, schema, mode)?;
 
-        // construct csr matrix
-        let mut cui_trimat: TriMat<u32> = TriMat::new((user_size, item_size));
+        Ok((builder.build(), report))
+    }
 
-        dataset::read_test_data(Path::new(&path), 0, |e| {
-            let events = e.to_events();
+    /// Loads every shard of a sharded input, see [`DatasetBuilder::read_shards`].
+    pub fn from_shards<P: AsRef<Path>>(
+        pattern: P,
+        format: &InputFormat,
+        mode: ParseMode,
+    ) -> Result<(Self, LoadReport), DatasetError> {
+        let mut builder = DatasetBuilder::new();
+        let report = builder.read_shards(pattern, format, mode)?;
 
-            events.iter().for_each(|e| {
-                let user_idx = user_idx.get_idx(e.user_id().to_string());
-                let item_idx = item_idx.get_idx(e.target_id().to_string());
+        Ok((builder.build(), report))
+    }
 
-                cui_trimat.add_triplet(user_idx, item_idx, 1);
-            });
-        });
+    /// Loads the ratings of a public benchmark file.
+    ///
+    /// A user rating the same item more than once keeps their latest rating. The dataset holds
+    /// [`FeedbackKind::Explicit`] ratings on a 1 to 5 scale, 0.5 to 5 for `MovieLensCsv`. The
+    /// scale of `RatingsTsv` files is the range of the ratings they hold, which fails with
+    /// [`DatasetError::InvalidConfig`] when they hold a single distinct rating, set the scale
+    /// with [`DatasetBuilder::with_feedback`] then.
+    pub fn from_benchmark<P: AsRef<Path>>(
+        path: P,
+        format: BenchmarkFormat,
+        mode: ParseMode,
+    ) -> Result<(Self, LoadReport), DatasetError> {
+        let scale = match format {
+            BenchmarkFormat::MovieLensCsv => Some(FeedbackKind::rating_scale(0.5, 5.0)?),
+            BenchmarkFormat::RatingsTsv => None,
+            _ => Some(FeedbackKind::rating_scale(1.0, 5.0)?),
+        };
+        let mut builder = DatasetBuilder::new().with_aggregation(Aggregation::Latest);
+        let report = builder.read_benchmark(path, format, mode)?;
 
-        let cui: CsMat<u32> = cui_trimat.to_csr();
-        let ciu: CsMat<u32> = cui.clone().transpose_into();
+        let mut dataset = builder.build();
+        dataset.parameters.feedback = match scale {
+            Some(scale) => scale,
+            None => {
+                let ratings = dataset.cui.data();
+                let min = ratings.iter().copied().fold(f64::INFINITY, f64::min);
+                let max = ratings.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                FeedbackKind::rating_scale(min, max)?
+            }
+        };
 
-        println!("shape of the user-item matrix: {:?}", cui.shape());
+        Ok((dataset, report))
+    }
 
-        Dataset {
-            cui: cui,
-            ciu: ciu,
-            user_idx,
-            item_idx,
+    /// Loads a delimited file whose columns are described by `schema`.
+    ///
+    /// With [`ParseMode::Lenient`] rows that could not be read are left out of the dataset and
+    /// listed in the returned [`LoadReport`].
+    pub fn from_csv<P: AsRef<Path>>(
+        path: P,
+        schema: &CsvSchema,
+        mode: ParseMode,
+    ) -> Result<(Self, LoadReport), DatasetError> {
+        let mut builder = DatasetBuilder::new();
+        let report = builder.read_csv(path, schema, mode)?;
+
+        Ok((builder.build(), report))
+    }
+
+    pub fn user_idx(&self) -> &ItemIndex {
+        &self.user_idx
+    }
+
+    pub fn item_idx(&self) -> &ItemIndex {
+        &self.item_idx
+    }
+
+    /// Item-user matrix, the transpose of `cui` stored in CSR so that rows are items.
+    pub fn ciu(&self) -> &CsMat<f64> {
+        &self.ciu
+    }
+
+    pub fn parameters(&self) -> &BuildParameters {
+        &self.parameters
+    }
+
+    pub fn feedback(&self) -> FeedbackKind {
+        self.parameters.feedback
+    }
+
+    pub(crate) fn with_parameters(mut self, parameters: BuildParameters) -> Self {
+        self.parameters = parameters;
+        self
+    }
+
+    pub(crate) fn last_seen_matrix(&self) -> Option<&CsMat<i64>> {
+        self.last_seen.as_ref()
+    }
+
+    /// Whether the events the dataset was built from carried timestamps.
+    pub fn has_timestamps(&self) -> bool {
+        self.last_seen.is_some()
+    }
+
+    /// Time of the latest interaction of the user with the item, if it was recorded.
+    pub fn last_seen(&self, user_idx: usize, item_idx: usize) -> Option<DateTime<Utc>> {
+        self.last_seen_millis(user_idx, item_idx)
+            .and_then(|t| Utc.timestamp_millis_opt(t).single())
+    }
+
+    pub(crate) fn last_seen_millis(&self, user_idx: usize, item_idx: usize) -> Option<i64> {
+        self.last_seen
+            .as_ref()
+            .and_then(|m| m.get(user_idx, item_idx))
+            .copied()
+    }
+
+    /// Attaches the interaction times, a matrix in the shape of `cui` holding unix milliseconds.
+    pub(crate) fn with_last_seen(mut self, last_seen: Option<CsMat<i64>>) -> Self {
+        self.last_seen = last_seen.map(CsMat::into_csr);
+        self
+    }
+
+    /// Splits the interactions into two datasets with the same shape and indexes, the ones
+    /// flagged in `second` go to the second dataset. Flags follow the order of `cui.iter()`.
+    pub(crate) fn partition(&self, second: &[bool]) -> (Dataset, Dataset) {
+        let shape = self.cui.shape();
+        let mut first_cui = TriMat::new(shape);
+        let mut second_cui = TriMat::new(shape);
+        let mut first_last_seen = TriMat::new(shape);
+        let mut second_last_seen = TriMat::new(shape);
+
+        for ((&value, (user, item)), &to_second) in self.cui.iter().zip(second) {
+            let (cui, last_seen) = if to_second {
+                (&mut second_cui, &mut second_last_seen)
+            } else {
+                (&mut first_cui, &mut first_last_seen)
+            };
+            cui.add_triplet(user, item, value);
+            if let Some(t) = self.last_seen_millis(user, item) {
+                last_seen.add_triplet(user, item, t);
+            }
         }
+
+        let first = Dataset::from_cui(
+            first_cui.to_csr(),
+            self.user_idx.clone(),
+            self.item_idx.clone(),
+        )
+        .with_last_seen(self.has_timestamps().then(|| first_last_seen.to_csr()))
+        .with_parameters(self.parameters.clone());
+        let second = Dataset::from_cui(
+            second_cui.to_csr(),
+            self.user_idx.clone(),
+            self.item_idx.clone(),
+        )
+        .with_last_seen(self.has_timestamps().then(|| second_last_seen.to_csr()))
+        .with_parameters(self.parameters.clone());
+
+        (first, second)
     }
 
-    pub fn from_csv_example() -> Self {
-        let mut user_idx = ItemIndex::new();
-        let mut item_idx = ItemIndex::new();
+    /// Keeps the flagged users and items, re-indexing both compactly in their current order.
+    pub(crate) fn retain(&self, keep_users: &[bool], keep_items: &[bool]) -> Dataset {
+        let (user_idx, user_map) = compact_index(&self.user_idx, keep_users);
+        let (item_idx, item_map) = compact_index(&self.item_idx, keep_items);
 
-        // learn the indexes in the data. Also gives us the information about how many users and items in it.
-        // might need to find a better solution in the future because we need to go through the dataset twice in this case.
-        dataset::read_ecommerce_data(Path::new("./data/data.csv"), |e| {
-            let _user_idx = user_idx.get_idx(e.user_id().to_string());
-            let _item_idx = item_idx.get_idx(e.target_id().to_string());
-        });
+        self.remap(user_idx, &user_map, item_idx, &item_map)
+    }
 
-        let user_size = user_idx.size();
-        let item_size = item_idx.size();
+    /// Re-expresses the interactions in the order of other indexes, e.g. the ones of a previous
+    /// dataset, so that models of both can be compared or warm-started. Users and items missing
+    /// from the given indexes are dropped, merge the indexes first to keep them, see
+    /// [`ItemIndex::merge`].
+    pub fn reindex(&self, user_idx: &ItemIndex, item_idx: &ItemIndex) -> Dataset {
+        let users = self.user_idx.remap_to(user_idx);
+        let items = self.item_idx.remap_to(item_idx);
 
-        // construct csr matrix
-        let mut cui_trimat: TriMat<u32> = TriMat::new((user_size, item_size));
+        self.remap(
+            user_idx.clone(),
+            users.as_slice(),
+            item_idx.clone(),
+            items.as_slice(),
+        )
+    }
 
-        dataset::read_ecommerce_data(Path::new("./data/data.csv"), |e| {
-            let user_idx = user_idx.get_idx(e.user_id().to_string());
-            let item_idx = item_idx.get_idx(e.target_id().to_string());
+    /// Moves every interaction to the indexes its user and item map to, dropping the ones of
+    /// unmapped users or items.
+    fn remap(
+        &self,
+        user_idx: ItemIndex,
+        user_map: &[Option<usize>],
+        item_idx: ItemIndex,
+        item_map: &[Option<usize>],
+    ) -> Dataset {
+        let shape = (user_idx.size(), item_idx.size());
+        let mut cui_trimat = TriMat::new(shape);
+        let mut last_seen_trimat = TriMat::new(shape);
+        for (&value, (user, item)) in self.cui.iter() {
+            if let (Some(new_user), Some(new_item)) = (user_map[user], item_map[item]) {
+                cui_trimat.add_triplet(new_user, new_item, value);
+                if let Some(t) = self.last_seen_millis(user, item) {
+                    last_seen_trimat.add_triplet(new_user, new_item, t);
+                }
+            }
+        }
 
-            cui_trimat.add_triplet(user_idx, item_idx, 1);
-        });
+        Dataset::from_cui(cui_trimat.to_csr(), user_idx, item_idx)
+            .with_last_seen(self.has_timestamps().then(|| last_seen_trimat.to_csr()))
+            .with_parameters(self.parameters.clone())
+    }
+}
+
+/// Index of the kept entries, and where each old index ended up in it.
+fn compact_index(index: &ItemIndex, keep: &[bool]) -> (ItemIndex, Vec<Option<usize>>) {
+    let mut compact = ItemIndex::new();
+    let mapping = keep
+        .iter()
+        .enumerate()
+        .map(|(idx, &kept)| {
+            kept.then(|| compact.get_or_insert(index.get_item(idx).unwrap()))
+                .flatten()
+        })
+        .collect();
+
+    (compact, mapping)
+}
+
+impl FromIterator<Event> for Dataset {
+    fn from_iter<T: IntoIterator<Item = Event>>(iter: T) -> Self {
+        Dataset::from_events(iter)
+    }
+}
+
+/// How loaders react to records they can not parse.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Fail on the first bad record.
+    #[default]
+    Strict,
+    /// Skip bad records and list them in the [`LoadReport`].
+    Lenient,
+}
+
+/// A row of an input file that was left out of the [`Dataset`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SkippedRecord {
+    pub path: PathBuf,
+    /// 1-based line number in the input file.
+    pub line: u64,
+    pub reason: String,
+}
+
+/// Summary of a load, so that dropped rows do not go unnoticed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LoadReport {
+    pub records_read: usize,
+    pub events_loaded: usize,
+    pub skipped: Vec<SkippedRecord>,
+}
+
+impl LoadReport {
+    pub fn records_skipped(&self) -> usize {
+        self.skipped.len()
+    }
+
+    /// Adds up the report of another file, e.g. another shard of the same input.
+    pub fn merge(&mut self, other: LoadReport) {
+        self.records_read += other.records_read;
+        self.events_loaded += other.events_loaded;
+        self.skipped.extend(other.skipped);
+    }
+
+    /// Records a bad line, or turns it into an error when parsing strictly.
+    pub(crate) fn reject(
+        &mut self,
+        mode: ParseMode,
+        path: &Path,
+        line: u64,
+        reason: String,
+    ) -> Result<(), DatasetError> {
+        match mode {
+            ParseMode::Strict => Err(DatasetError::parse(path, line, reason)),
+            ParseMode::Lenient => {
+                self.skipped.push(SkippedRecord {
+                    path: path.to_path_buf(),
+                    line,
+                    reason,
+                });
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Maps each [`EventType`] to the weight its events contribute to the user-item matrix.
+///
+/// The value of an event in the matrix is its own [`Event::weight`] scaled by the weight of its
+/// type, types without an entry use the default weight.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EventWeights {
+    weights: HashMap<EventType, f64>,
+    default_weight: f64,
+}
+
+impl EventWeights {
+    /// Every event type weighs the same, `1.0`.
+    pub fn new() -> Self {
+        Self {
+            weights: HashMap::new(),
+            default_weight: 1.0,
+        }
+    }
+
+    pub fn with(mut self, event_type: EventType, weight: f64) -> Self {
+        self.weights.insert(event_type, weight);
+        self
+    }
+
+    pub fn with_default_weight(mut self, weight: f64) -> Self {
+        self.default_weight = weight;
+        self
+    }
+
+    pub fn weight_of(&self, event_type: &EventType) -> f64 {
+        self.weights
+            .get(event_type)
+            .copied()
+            .unwrap_or(self.default_weight)
+    }
+
+    /// Value of the event in the user-item matrix.
+    pub fn value_of(&self, event: &Event) -> f64 {
+        event.weight() * self.weight_of(event.event_type())
+    }
+}
+
+impl Default for EventWeights {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Exponentially decays the value of events by their age, so that old interactions contribute
+/// less to the user-item matrix.
+///
+/// An event `half_life` older than the reference time counts half as much as a fresh one. Events
+/// without a timestamp, or newer than the reference time, are not decayed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TimeDecay {
+    #[serde(with = "duration_millis")]
+    half_life: Duration,
+    reference: Option<DateTime<Utc>>,
+}
+
+impl TimeDecay {
+    /// Decays relative to the most recent event of the dataset.
+    ///
+    /// Fails with [`DatasetError::InvalidConfig`] when `half_life` is not positive.
+    pub fn new(half_life: Duration) -> Result<Self, DatasetError> {
+        if half_life <= Duration::zero() {
+            return Err(DatasetError::InvalidConfig(format!(
+                "half life should be positive, got {}",
+                half_life
+            )));
+        }
+
+        Ok(Self {
+            half_life,
+            reference: None,
+        })
+    }
+
+    /// Decays relative to a fixed point in time instead of the most recent event.
+    pub fn relative_to(mut self, reference: DateTime<Utc>) -> Self {
+        self.reference = Some(reference);
+        self
+    }
+
+    pub fn half_life(&self) -> Duration {
+        self.half_life
+    }
+
+    pub fn reference(&self) -> Option<DateTime<Utc>> {
+        self.reference
+    }
+
+    /// Multiplier for an event that happened at `timestamp` when decaying relative to `reference`.
+    pub fn factor(&self, timestamp: DateTime<Utc>, reference: DateTime<Utc>) -> f64 {
+        let age = (reference - timestamp).num_milliseconds().max(0) as f64;
+        let half_life = self.half_life.num_milliseconds() as f64;
+
+        0.5f64.powf(age / half_life)
+    }
+}
+
+/// Whether the values of the user-item matrix are implicit preferences or explicit ratings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum FeedbackKind {
+    /// Interactions such as views or purchases, larger values meaning a stronger preference.
+    #[default]
+    Implicit,
+    /// Ratings given on a `min..=max` scale, such as 1 to 5 stars.
+    Explicit { min: f64, max: f64 },
+}
+
+impl FeedbackKind {
+    /// Fails with [`DatasetError::InvalidConfig`] when the scale is empty.
+    pub fn rating_scale(min: f64, max: f64) -> Result<Self, DatasetError> {
+        if min.is_nan() || max.is_nan() || min >= max {
+            return Err(DatasetError::InvalidConfig(format!(
+                "rating scale {}..={} should not be empty",
+                min, max
+            )));
+        }
+        Ok(FeedbackKind::Explicit { min, max })
+    }
+
+    pub fn is_explicit(&self) -> bool {
+        matches!(self, FeedbackKind::Explicit { .. })
+    }
+
+    /// Brings a predicted value back into the rating scale, implicit values are left as they are.
+    pub fn clamp(&self, value: f64) -> f64 {
+        match self {
+            FeedbackKind::Implicit => value,
+            FeedbackKind::Explicit { min, max } => value.clamp(*min, *max),
+        }
+    }
+}
+
+/// How the events of the same (user, item) pair are combined into a single matrix value.
+///
+/// The values being combined are event values after [`EventWeights`] and [`TimeDecay`] are applied.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Aggregation {
+    /// Sum of the values.
+    #[default]
+    Sum,
+    /// `1.0` for any interaction.
+    Binary,
+    /// Number of events, regardless of their values.
+    Count,
+    /// `ln(1 + count)`, so that heavy repeaters do not dominate.
+    LogCount,
+    /// Largest value.
+    Max,
+    /// Value of the most recent event. Events without a timestamp count as older than the ones
+    /// with, ties go to the event pushed last.
+    Latest,
+}
+
+impl Aggregation {
+    fn aggregate(&self, interactions: &[Interaction]) -> f64 {
+        match self {
+            Aggregation::Sum => interactions.iter().map(|i| i.value).sum(),
+            Aggregation::Binary => 1.0,
+            Aggregation::Count => interactions.len() as f64,
+            Aggregation::LogCount => (interactions.len() as f64).ln_1p(),
+            Aggregation::Max => interactions
+                .iter()
+                .map(|i| i.value)
+                .fold(f64::NEG_INFINITY, f64::max),
+            Aggregation::Latest => interactions
+                .iter()
+                .max_by_key(|i| i.timestamp)
+                .map(|i| i.value)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Collects [`Event`]s into a [`Dataset`], assigning user and item indexes as the events arrive.
+///
+/// Events can be pushed one at a time or in bulk through [`Extend`], so any source can
+/// feed the builder without going through a file first.
+#[derive(Debug, Default)]
+pub struct DatasetBuilder {
+    user_idx: ItemIndex,
+    item_idx: ItemIndex,
+    event_weights: EventWeights,
+    time_decay: Option<TimeDecay>,
+    aggregation: Aggregation,
+    feedback: FeedbackKind,
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Debug)]
+struct Interaction {
+    user_idx: usize,
+    item_idx: usize,
+    value: f64,
+    timestamp: Option<DateTime<Utc>>,
+}
+
+impl DatasetBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_event_weights(mut self, event_weights: EventWeights) -> Self {
+        self.event_weights = event_weights;
+        self
+    }
+
+    /// Decays the value of events by their age, see [`TimeDecay`].
+    pub fn with_time_decay(mut self, time_decay: TimeDecay) -> Self {
+        self.time_decay = Some(time_decay);
+        self
+    }
 
-        let cui: CsMat<u32> = cui_trimat.to_csr();
-        let ciu: CsMat<u32> = cui.clone();
+    /// Sets how repeated interactions of a user with the same item are combined, see [`Aggregation`].
+    pub fn with_aggregation(mut self, aggregation: Aggregation) -> Self {
+        self.aggregation = aggregation;
+        self
+    }
 
-        println!("shape of the user-item matrix: {:?}", cui.shape());
+    /// Marks the matrix values as implicit preferences or ratings, see [`FeedbackKind`]. Ratings
+    /// are usually combined with [`Aggregation::Latest`] or [`Aggregation::Max`].
+    pub fn with_feedback(mut self, feedback: FeedbackKind) -> Self {
+        self.feedback = feedback;
+        self
+    }
 
-        Dataset {
-            cui: cui,
-            ciu: ciu, // is this field necessary? If so do not forget to provide the correct value!
+    pub fn push(&mut self, event: Event) {
+        // the indexes of a builder are only frozen once they are moved into the dataset.
+        let (Some(user_idx), Some(item_idx)) = (
+            self.user_idx.get_or_insert(event.user_id()),
+            self.item_idx.get_or_insert(event.target_id()),
+        ) else {
+            unreachable!("the indexes of a builder are never frozen");
+        };
+        let value = self.event_weights.value_of(&event);
+
+        self.interactions.push(Interaction {
             user_idx,
             item_idx,
+            value,
+            timestamp: event.timestamp(),
+        });
+    }
+
+    /// Pushes the events of a file in any of the supported formats. Gzip and zstd compressed
+    /// files are decompressed on the fly.
+    pub fn read<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        format: &InputFormat,
+        mode: ParseMode,
+    ) -> Result<LoadReport, DatasetError> {
+        dataset::read_events(path.as_ref(), format, mode, |e| self.push(e))
+    }
+
+    /// Pushes the events of a sharded input, `pattern` being a directory of shards or a glob
+    /// pattern such as `exports/part-*.jsonl.gz`.
+    ///
+    /// Shards are parsed in parallel but pushed in the order of their paths, so indexes are
+    /// assigned the same way on every run.
+    pub fn read_shards<P: AsRef<Path>>(
+        &mut self,
+        pattern: P,
+        format: &InputFormat,
+        mode: ParseMode,
+    ) -> Result<LoadReport, DatasetError> {
+        let paths = dataset::shard_paths(pattern.as_ref())?;
+        let threads = thread::available_parallelism().map_or(4, |n| n.get());
+
+        dataset::read_shards(&paths, format, mode, threads, |e| self.push(e))
+    }
+
+    /// Pushes the events of a jsonl file whose fields are described by `schema`.
+    pub fn read_jsonl<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        schema: &JsonlSchema,
+        mode: ParseMode,
+    ) -> Result<LoadReport, DatasetError> {
+        dataset::read_jsonl(path.as_ref(), schema, mode, |e| self.push(e))
+    }
+
+    /// Pushes the ratings of a public benchmark file, keeping their timestamps.
+    pub fn read_benchmark<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        format: BenchmarkFormat,
+        mode: ParseMode,
+    ) -> Result<LoadReport, DatasetError> {
+        self.read(path, &InputFormat::Benchmark(format), mode)
+    }
+
+    /// Pushes the events of a delimited file whose columns are described by `schema`.
+    pub fn read_csv<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        schema: &CsvSchema,
+        mode: ParseMode,
+    ) -> Result<LoadReport, DatasetError> {
+        dataset::read_csv(path.as_ref(), schema, mode, |e| self.push(e))
+    }
+
+    /// Number of interactions pushed so far, duplicates included.
+    pub fn len(&self) -> usize {
+        self.interactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.interactions.is_empty()
+    }
+
+    pub fn build(self) -> Dataset {
+        let shape = (self.user_idx.size(), self.item_idx.size());
+
+        let decay = self.time_decay.as_ref().and_then(|decay| {
+            let reference = decay
+                .reference()
+                .or_else(|| self.interactions.iter().filter_map(|i| i.timestamp).max())?;
+            Some((decay, reference))
+        });
+
+        let mut interactions = self.interactions;
+        if let Some((decay, reference)) = decay {
+            for interaction in interactions.iter_mut() {
+                if let Some(timestamp) = interaction.timestamp {
+                    interaction.value *= decay.factor(timestamp, reference);
+                }
+            }
         }
+
+        // the sort is stable, so the events of a (user, item) pair stay in the order they were pushed.
+        interactions.sort_by_key(|i| (i.user_idx, i.item_idx));
+
+        let mut cui_trimat: TriMat<f64> = TriMat::with_capacity(shape, interactions.len());
+        let mut last_seen_trimat: TriMat<i64> = TriMat::new(shape);
+        for group in
+            interactions.chunk_by(|a, b| (a.user_idx, a.item_idx) == (b.user_idx, b.item_idx))
+        {
+            let (user_idx, item_idx) = (group[0].user_idx, group[0].item_idx);
+
+            cui_trimat.add_triplet(user_idx, item_idx, self.aggregation.aggregate(group));
+            if let Some(t) = group.iter().filter_map(|i| i.timestamp).max() {
+                last_seen_trimat.add_triplet(user_idx, item_idx, t.timestamp_millis());
+            }
+        }
+
+        let last_seen = (last_seen_trimat.nnz() > 0).then(|| last_seen_trimat.to_csr());
+
+        let parameters = BuildParameters {
+            event_weights: self.event_weights,
+            time_decay: self.time_decay,
+            aggregation: self.aggregation,
+            feedback: self.feedback,
+        };
+
+        Dataset::from_cui(cui_trimat.to_csr(), self.user_idx, self.item_idx)
+            .with_last_seen(last_seen)
+            .with_parameters(parameters)
+    }
+}
+
+impl Extend<Event> for DatasetBuilder {
+    fn extend<T: IntoIterator<Item = Event>>(&mut self, iter: T) {
+        iter.into_iter().for_each(|e| self.push(e));
     }
+}
 
-    pub fn user_idx(&self) -> &ItemIndex {
-        &self.user_idx
+mod duration_millis {
+    use chrono::Duration;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(duration.num_milliseconds())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        i64::deserialize(deserializer).map(Duration::milliseconds)
     }
 }
 
 #[cfg(test)]
 mod dataset_test {
-    use super::Dataset;
+    use std::{env, fs, io::Write};
+
+    use chrono::{Duration, TimeZone, Utc};
+
+    use crate::core::{
+        error::DatasetError,
+        model::{Event, EventType},
+        schema::{BenchmarkFormat, CsvSchema, InputFormat, JsonlSchema},
+    };
+
+    use super::{
+        Aggregation, Dataset, DatasetBuilder, EventWeights, FeedbackKind, ParseMode, TimeDecay,
+    };
 
     #[test]
     fn test_loading_jsonl() {
-        let dataset = Dataset::from_jsonl("./data/test_data.jsonl".to_string());
+        let path = env::temp_dir().join("rs_mender_loading.jsonl");
+        fs::write(
+            &path,
+            "{\"user_id\": \"u-1\", \"deliveries\": [\"p-1\", \"p-2\"]}\n\
+             {\"user_id\": \"u-2\", \"deliveries\": [\"p-2\"]}\n\
+             {\"user_id\": \"u-3\", \"deliveries\": \n\
+             {\"user_id\": \"u-3\", \"deliveries\": [\"p-3\"]}\n",
+        )
+        .unwrap();
+
+        match Dataset::from_jsonl(&path, &JsonlSchema::deliveries(), ParseMode::Strict) {
+            Err(DatasetError::Parse { line, .. }) => assert_eq!(3, line),
+            other => panic!("expected a parse error, got {:?}", other.map(|(_, r)| r)),
+        }
+
+        let (dataset, report) =
+            Dataset::from_jsonl(&path, &JsonlSchema::deliveries(), ParseMode::Lenient).unwrap();
+        assert_eq!((3, 3), dataset.cui.shape());
+        assert_eq!(4, report.records_read);
+        assert_eq!(4, report.events_loaded);
+        assert_eq!(
+            vec![3],
+            report.skipped.iter().map(|s| s.line).collect::<Vec<_>>()
+        );
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn should_fail_loading_missing_file() {
+        let result = Dataset::from_jsonl(
+            "./does/not/exist.jsonl",
+            &JsonlSchema::deliveries(),
+            ParseMode::Lenient,
+        );
+
+        assert!(matches!(result, Err(DatasetError::Io { .. })));
+    }
+
+    #[test]
+    fn should_build_dataset_in_a_single_pass() {
+        let events = vec![
+            Event::new("user-1".to_string(), "product-1".to_string()),
+            Event::new("user-1".to_string(), "product-2".to_string()),
+            Event::new("user-2".to_string(), "product-3".to_string()),
+            Event::new("user-1".to_string(), "product-1".to_string()),
+        ];
+
+        let mut builder = DatasetBuilder::new();
+        builder.extend(events);
+        builder.push(Event::new("user-3".to_string(), "product-2".to_string()));
+        assert_eq!(5, builder.len());
+
+        let dataset = builder.build();
+
+        assert_eq!((3, 3), dataset.cui.shape());
+        assert_eq!((3, 3), dataset.ciu().shape());
+
+        // indexes are assigned in order of appearance
+        let (user_1, product_1, product_2) = (0, 0, 1);
+        assert_eq!(Some("user-1"), dataset.user_idx.get_item(user_1));
+        assert_eq!(Some("product-2"), dataset.item_idx.get_item(product_2));
+
+        assert_eq!(Some(&2.0), dataset.cui.get(user_1, product_1));
+        assert_eq!(Some(&2.0), dataset.ciu().get(product_1, user_1));
+        assert_eq!(2, dataset.ciu().outer_view(product_2).unwrap().nnz());
+    }
+
+    #[test]
+    fn should_collect_events_into_dataset() {
+        let dataset: Dataset = (0..10)
+            .map(|i| Event::new(format!("user-{}", i % 2), format!("product-{}", i % 5)))
+            .collect();
+
+        assert_eq!((2, 5), dataset.cui.shape());
+        assert_eq!(10, dataset.cui.nnz());
+    }
+
+    #[test]
+    fn should_load_csv_with_column_mapping() {
+        let path = env::temp_dir().join("rs_mender_column_mapping.csv");
+        fs::write(
+            &path,
+            "product;amount;customer\n\
+             p-1;1;c-1\n\
+             p-2;2;c-1\n\
+             p-2;;c-2\n\
+             p-3;x;c-2\n\
+             p-1;1\n\
+             p-3;4;c-3\n",
+        )
+        .unwrap();
+
+        let schema = CsvSchema::new("customer", "product")
+            .with_weight("amount")
+            .with_delimiter(b';');
+        assert!(matches!(
+            Dataset::from_csv(&path, &schema, ParseMode::Strict),
+            Err(DatasetError::Parse { line: 4, .. })
+        ));
+
+        let (dataset, report) = Dataset::from_csv(&path, &schema, ParseMode::Lenient).unwrap();
+
+        assert_eq!(6, report.records_read);
+        assert_eq!(3, report.events_loaded);
+        assert_eq!(
+            vec![4, 5, 6],
+            report.skipped.iter().map(|s| s.line).collect::<Vec<_>>()
+        );
+        assert_eq!((2, 3), dataset.cui.shape());
+        assert_eq!(Some(&2.0), dataset.cui.get(0, 1));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn should_weight_events_by_type() {
+        let weights = EventWeights::new()
+            .with(EventType::View, 0.5)
+            .with(EventType::Purchase, 4.0);
+
+        let mut builder = DatasetBuilder::new().with_event_weights(weights);
+        builder.extend(vec![
+            Event::new("u-1".to_string(), "p-1".to_string()).with_event_type(EventType::View),
+            Event::new("u-1".to_string(), "p-1".to_string()).with_event_type(EventType::Purchase),
+            Event::new("u-1".to_string(), "p-2".to_string())
+                .with_event_type(EventType::Rating)
+                .with_weight(3.0),
+            Event::new("u-2".to_string(), "p-2".to_string()),
+        ]);
+        let dataset = builder.build();
+
+        assert_eq!(Some(&4.5), dataset.cui.get(0, 0));
+        assert_eq!(Some(&3.0), dataset.cui.get(0, 1));
+        assert_eq!(Some(&1.0), dataset.cui.get(1, 1));
+    }
+
+    #[test]
+    fn should_decay_old_events() {
+        let now = Utc.with_ymd_and_hms(2023, 8, 1, 0, 0, 0).unwrap();
+        let event = |item: &str, days_ago: i64| {
+            Event::new("u-1".to_string(), item.to_string())
+                .with_timestamp(now - Duration::days(days_ago))
+        };
+
+        let mut builder =
+            DatasetBuilder::new().with_time_decay(TimeDecay::new(Duration::days(7)).unwrap());
+        builder.extend(vec![
+            event("p-1", 0),
+            event("p-2", 7),
+            event("p-3", 14),
+            Event::new("u-1".to_string(), "p-4".to_string()),
+        ]);
+        let dataset = builder.build();
+
+        assert_eq!(Some(&1.0), dataset.cui.get(0, 0));
+        assert_eq!(Some(&0.5), dataset.cui.get(0, 1));
+        assert_eq!(Some(&0.25), dataset.cui.get(0, 2));
+        assert_eq!(Some(&1.0), dataset.cui.get(0, 3));
+
+        let decay = TimeDecay::new(Duration::days(7))
+            .unwrap()
+            .relative_to(now + Duration::days(7));
+        let mut builder = DatasetBuilder::new().with_time_decay(decay);
+        builder.push(event("p-1", 0));
+
+        assert_eq!(Some(&0.5), builder.build().cui.get(0, 0));
+    }
+
+    #[test]
+    fn should_aggregate_repeated_interactions() {
+        let now = Utc.with_ymd_and_hms(2023, 8, 1, 0, 0, 0).unwrap();
+        let events = vec![
+            Event::new("u-1".to_string(), "p-1".to_string())
+                .with_weight(2.0)
+                .with_timestamp(now),
+            Event::new("u-1".to_string(), "p-1".to_string())
+                .with_weight(5.0)
+                .with_timestamp(now - Duration::days(1)),
+            Event::new("u-1".to_string(), "p-1".to_string()).with_weight(3.0),
+            Event::new("u-2".to_string(), "p-1".to_string()).with_weight(4.0),
+        ];
+
+        let value_with = |aggregation: Aggregation| {
+            let mut builder = DatasetBuilder::new().with_aggregation(aggregation);
+            builder.extend(events.clone());
+            let dataset = builder.build();
+
+            assert_eq!(2, dataset.cui.nnz());
+            *dataset.cui.get(0, 0).unwrap()
+        };
+
+        assert_eq!(10.0, value_with(Aggregation::Sum));
+        assert_eq!(1.0, value_with(Aggregation::Binary));
+        assert_eq!(3.0, value_with(Aggregation::Count));
+        assert_eq!(4f64.ln(), value_with(Aggregation::LogCount));
+        assert_eq!(5.0, value_with(Aggregation::Max));
+        assert_eq!(2.0, value_with(Aggregation::Latest));
+    }
+
+    #[test]
+    fn should_load_benchmark_formats() {
+        let files = [
+            (
+                BenchmarkFormat::MovieLens100K,
+                "rs_mender_u.data",
+                "196\t242\t3\t881250949\n186\t302\t3\t891717742\n196\t302\t4\t881251949\n",
+            ),
+            (
+                BenchmarkFormat::MovieLensDat,
+                "rs_mender_ratings.dat",
+                "196::242::3::881250949\n186::302::3::891717742\n196::302::4::881251949\n",
+            ),
+            (
+                BenchmarkFormat::MovieLensCsv,
+                "rs_mender_ratings.csv",
+                "userId,movieId,rating,timestamp\n196,242,3.0,881250949\n186,302,3.0,891717742\n196,302,4.0,881251949\n",
+            ),
+            (
+                BenchmarkFormat::AmazonReviews,
+                "rs_mender_reviews.jsonl",
+                "{\"reviewerID\": \"196\", \"asin\": \"242\", \"overall\": 3.0, \"unixReviewTime\": 881250949}\n\
+                 {\"user_id\": \"186\", \"asin\": \"302\", \"parent_asin\": \"300\", \"rating\": 3.0, \"timestamp\": 891717742000}\n\
+                 {\"reviewerID\": \"196\", \"asin\": \"302\", \"overall\": 4.0, \"unixReviewTime\": 881251949}\n",
+            ),
+        ];
+
+        for (format, name, content) in files {
+            let path = env::temp_dir().join(name);
+            fs::write(&path, content).unwrap();
+
+            let (dataset, report) =
+                Dataset::from_benchmark(&path, format, ParseMode::Strict).unwrap();
+
+            assert_eq!(3, report.events_loaded, "{:?}", format);
+            assert_eq!((2, 2), dataset.cui.shape(), "{:?}", format);
+            assert_eq!(Some(&4.0), dataset.cui.get(0, 1), "{:?}", format);
+            assert!(dataset.feedback().is_explicit(), "{:?}", format);
+            assert_eq!(
+                Some(Utc.timestamp_opt(891717742, 0).unwrap()),
+                dataset.last_seen(1, 1),
+                "{:?}",
+                format
+            );
+
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn should_infer_the_rating_scale_of_generic_ratings() {
+        let path = env::temp_dir().join("rs_mender_ratings.tsv");
+        fs::write(&path, "u-1\tp-1\t8\nu-1\tp-2\t10\nu-2\tp-1\t9\t881250949\n").unwrap();
+
+        let (dataset, _) =
+            Dataset::from_benchmark(&path, BenchmarkFormat::RatingsTsv, ParseMode::Strict).unwrap();
+
+        assert_eq!(
+            FeedbackKind::Explicit {
+                min: 8.0,
+                max: 10.0
+            },
+            dataset.feedback()
+        );
+        assert_eq!(Some(&10.0), dataset.cui.get(0, 1));
+
+        fs::write(&path, "u-1\tp-1\t8\nu-2\tp-1\t8\n").unwrap();
+        assert!(matches!(
+            Dataset::from_benchmark(&path, BenchmarkFormat::RatingsTsv, ParseMode::Strict),
+            Err(DatasetError::InvalidConfig(_))
+        ));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn should_read_compressed_shards_in_order() {
+        let dir = env::temp_dir().join("rs_mender_shards");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let shard = |user: &str| {
+            format!(
+                "{{\"user_id\": \"{}\", \"deliveries\": [\"p-1\", \"p-2\"]}}\n",
+                user
+            )
+        };
+        fs::write(dir.join("part-0.jsonl"), shard("u-0")).unwrap();
+
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(shard("u-1").as_bytes()).unwrap();
+        fs::write(dir.join("part-1.jsonl.gz"), gz.finish().unwrap()).unwrap();
+
+        let zst = zstd::encode_all(shard("u-2").as_bytes(), 0).unwrap();
+        fs::write(dir.join("part-2.jsonl.zst"), zst).unwrap();
+
+        fs::write(dir.join("_SUCCESS"), "").unwrap();
+
+        let (dataset, report) = Dataset::from_shards(
+            &dir,
+            &InputFormat::Jsonl(JsonlSchema::deliveries()),
+            ParseMode::Strict,
+        )
+        .unwrap();
+
+        assert_eq!(3, report.records_read);
+        assert_eq!(6, report.events_loaded);
+        assert_eq!((3, 2), dataset.cui.shape());
+        assert_eq!(
+            vec!["u-0", "u-1", "u-2"],
+            dataset.user_idx.items().collect::<Vec<_>>()
+        );
+
+        let (globbed, _) = Dataset::from_shards(
+            dir.join("part-*.jsonl.*"),
+            &InputFormat::Jsonl(JsonlSchema::deliveries()),
+            ParseMode::Strict,
+        )
+        .unwrap();
+        assert_eq!(
+            vec!["u-1", "u-2"],
+            globbed.user_idx.items().collect::<Vec<_>>()
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn should_load_jsonl_with_json_pointers() {
+        let path = env::temp_dir().join("rs_mender_json_pointers.jsonl");
+
+        fs::write(
+            &path,
+            "{\"user\": {\"id\": 1}, \"item\": \"p-1\", \"ts\": 1690000000, \"type\": \"view\"}\n\
+             {\"user\": {\"id\": 2}, \"item\": \"p-2\", \"ts\": \"2023-07-22T04:26:40Z\", \"type\": \"purchase\"}\n",
+        )
+        .unwrap();
+        let flat = JsonlSchema::new("/user/id", "/item")
+            .with_timestamp("/ts")
+            .with_event_type("type");
+        let weights = EventWeights::new().with(EventType::Purchase, 3.0);
+
+        let mut builder = DatasetBuilder::new().with_event_weights(weights);
+        builder.read_jsonl(&path, &flat, ParseMode::Strict).unwrap();
+        let dataset = builder.build();
+
+        assert_eq!(vec!["1", "2"], dataset.user_idx.items().collect::<Vec<_>>());
+        assert_eq!(Some(&3.0), dataset.cui.get(1, 1));
+        assert_eq!(
+            Some(Utc.timestamp_opt(1690000000, 0).unwrap()),
+            dataset.last_seen(1, 1)
+        );
+
+        fs::write(
+            &path,
+            "{\"customer\": \"u-1\", \"at\": 1690000000, \"basket\": [{\"sku\": \"p-1\", \"qty\": 2}, {\"sku\": \"p-2\", \"qty\": 1}]}\n\
+             {\"customer\": \"u-2\", \"at\": 1690000000, \"basket\": [{\"sku\": \"p-2\", \"qty\": 5}, {\"qty\": 1}]}\n",
+        )
+        .unwrap();
+        let nested = JsonlSchema::new("/customer", "/basket")
+            .with_item_id_in_array("/sku")
+            .with_weight("/qty")
+            .with_timestamp("/at");
+
+        let (dataset, report) = Dataset::from_jsonl(&path, &nested, ParseMode::Lenient).unwrap();
+
+        assert_eq!(2, report.events_loaded);
+        assert_eq!(
+            vec![2],
+            report.skipped.iter().map(|s| s.line).collect::<Vec<_>>()
+        );
+        assert_eq!(Some(&2.0), dataset.cui.get(0, 0));
+        assert_eq!(Some(&1.0), dataset.cui.get(0, 1));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn should_reindex_a_retrained_dataset_into_the_previous_order() {
+        let events = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(u, i)| Event::new(u.to_string(), i.to_string()))
+                .collect::<Dataset>()
+        };
+        let previous = events(&[("u-1", "p-1"), ("u-2", "p-2")]);
+        let retrained = events(&[("u-3", "p-3"), ("u-2", "p-2"), ("u-1", "p-2")]);
+
+        let (users, _) = previous.user_idx.merge(&retrained.user_idx);
+        let (items, _) = previous.item_idx.merge(&retrained.item_idx);
+        let aligned = retrained.reindex(&users, &items);
+
+        assert_eq!((3, 3), aligned.cui.shape());
+        assert_eq!(Some(&1.0), aligned.cui.get(0, 1));
+        assert_eq!(Some(&1.0), aligned.cui.get(1, 1));
+        assert_eq!(Some(&1.0), aligned.cui.get(2, 2));
+        assert_eq!(3, aligned.ciu().nnz());
+
+        let dropped = retrained.reindex(&previous.user_idx, &previous.item_idx);
+        assert_eq!((2, 2), dropped.cui.shape());
+        assert_eq!(2, dropped.cui.nnz());
     }
 }
@@ -0,0 +1,171 @@
+use std::{fs, path::Path};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{dataset::Dataset, error::DatasetError};
+
+/// A user or an item whose data is to be deleted.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Subject {
+    User(String),
+    Item(String),
+}
+
+/// Audit record of a deletion, confirming what was removed and from where.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeletionRecord {
+    pub subject: Subject,
+    /// Index the subject had before the deletion, the entries after it moved down by one.
+    pub former_idx: usize,
+    pub interactions_removed: usize,
+    /// What the subject was removed from, e.g. `dataset`, `u_matrix` or the path of a snapshot.
+    pub removed_from: Vec<String>,
+    pub removed_at: DateTime<Utc>,
+}
+
+impl Dataset {
+    /// Deletes a user with all their interactions from `cui`, `ciu` and the user index. Users
+    /// after it move down by one index, items are left as they are.
+    ///
+    /// Returns `None` when the user is not in the dataset.
+    pub fn remove_user(&mut self, user_id: &str) -> Option<DeletionRecord> {
+        self.remove(&Subject::User(user_id.to_string()))
+    }
+
+    /// Deletes an item with all its interactions, see [`remove_user`](Self::remove_user).
+    pub fn remove_item(&mut self, item_id: &str) -> Option<DeletionRecord> {
+        self.remove(&Subject::Item(item_id.to_string()))
+    }
+
+    /// Deletes a user or an item, see [`remove_user`](Self::remove_user).
+    pub fn remove(&mut self, subject: &Subject) -> Option<DeletionRecord> {
+        let mut keep_users = vec![true; self.user_idx.size()];
+        let mut keep_items = vec![true; self.item_idx.size()];
+
+        let (former_idx, interactions_removed) = match subject {
+            Subject::User(user_id) => {
+                let idx = self.user_idx.lookup(user_id)?;
+                keep_users[idx] = false;
+                (idx, self.cui.outer_view(idx).map_or(0, |row| row.nnz()))
+            }
+            Subject::Item(item_id) => {
+                let idx = self.item_idx.lookup(item_id)?;
+                keep_items[idx] = false;
+                (idx, self.ciu().outer_view(idx).map_or(0, |row| row.nnz()))
+            }
+        };
+
+        *self = self.retain(&keep_users, &keep_items);
+
+        Some(DeletionRecord {
+            subject: subject.clone(),
+            former_idx,
+            interactions_removed,
+            removed_from: vec!["dataset".to_string()],
+            removed_at: Utc::now(),
+        })
+    }
+
+    /// Deletes a user or an item from a snapshot written by [`Dataset::save`], replacing the file
+    /// only once the new snapshot is fully written.
+    ///
+    /// Returns `None`, leaving the file untouched, when the subject is not in the snapshot.
+    pub fn remove_from_snapshot<P: AsRef<Path>>(
+        path: P,
+        subject: &Subject,
+    ) -> Result<Option<DeletionRecord>, DatasetError> {
+        let path = path.as_ref();
+        let mut dataset = Dataset::load(path)?;
+        let Some(mut record) = dataset.remove(subject) else {
+            return Ok(None);
+        };
+
+        let mut staging = path.as_os_str().to_owned();
+        staging.push(".tmp");
+        dataset.save(&staging)?;
+        fs::rename(&staging, path).map_err(|e| DatasetError::io(path, e))?;
+
+        record.removed_from = vec![path.display().to_string()];
+        Ok(Some(record))
+    }
+}
+
+#[cfg(test)]
+mod erasure_test {
+    use std::{env, fs};
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::core::{dataset::Dataset, model::Event};
+
+    use super::Subject;
+
+    fn dataset() -> Dataset {
+        [
+            ("u-1", "p-1"),
+            ("u-2", "p-1"),
+            ("u-2", "p-2"),
+            ("u-3", "p-3"),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(t, (u, i))| {
+            Event::new(u.to_string(), i.to_string())
+                .with_timestamp(Utc.timestamp_opt(1_690_000_000 + t as i64, 0).unwrap())
+        })
+        .collect()
+    }
+
+    #[test]
+    fn should_remove_a_user_and_shift_the_following_ones() {
+        let mut dataset = dataset();
+
+        let record = dataset.remove_user("u-2").unwrap();
+
+        assert_eq!(Subject::User("u-2".to_string()), record.subject);
+        assert_eq!(1, record.former_idx);
+        assert_eq!(2, record.interactions_removed);
+        assert_eq!((2, 3), dataset.cui.shape());
+        assert_eq!(2, dataset.cui.nnz());
+        assert_eq!(2, dataset.ciu().nnz());
+        assert!(!dataset.user_idx().has_item("u-2"));
+        assert_eq!(Some("u-3"), dataset.user_idx().get_item(1));
+        assert_eq!(
+            Some(Utc.timestamp_opt(1_690_000_003, 0).unwrap()),
+            dataset.last_seen(1, 2)
+        );
+
+        assert_eq!(None, dataset.remove_user("u-2"));
+    }
+
+    #[test]
+    fn should_remove_an_item() {
+        let mut dataset = dataset();
+
+        let record = dataset.remove_item("p-1").unwrap();
+
+        assert_eq!(2, record.interactions_removed);
+        assert_eq!((3, 2), dataset.cui.shape());
+        assert_eq!(Some(&1.0), dataset.cui.get(1, 0));
+    }
+
+    #[test]
+    fn should_rewrite_snapshots_without_the_subject() {
+        let path = env::temp_dir().join("rs_mender_erasure.bin");
+        dataset().save(&path).unwrap();
+
+        let subject = Subject::User("u-1".to_string());
+        let record = Dataset::remove_from_snapshot(&path, &subject)
+            .unwrap()
+            .unwrap();
+        let unknown = Dataset::remove_from_snapshot(&path, &subject).unwrap();
+        let loaded = Dataset::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(vec![path.display().to_string()], record.removed_from);
+        assert_eq!(None, unknown);
+        assert_eq!((2, 3), loaded.cui.shape());
+        assert!(!loaded.user_idx().has_item("u-1"));
+    }
+}
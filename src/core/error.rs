@@ -0,0 +1,169 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    io,
+    path::PathBuf,
+};
+
+/// Errors raised while reading input files into a [`Dataset`](super::dataset::Dataset).
+#[derive(Debug)]
+pub enum DatasetError {
+    /// The file could not be opened or read.
+    Io { path: PathBuf, source: io::Error },
+    /// A record could not be turned into events.
+    Parse {
+        path: PathBuf,
+        line: u64,
+        reason: String,
+    },
+    /// The file does not match the schema it is read with, e.g. a column is missing.
+    Schema { path: PathBuf, reason: String },
+    /// A dataset snapshot is not in the expected format, or does not match its checksum.
+    Corrupted { path: PathBuf, reason: String },
+    /// The operation needs event timestamps, but the dataset was built from events without them,
+    /// or an event to group into sessions has none.
+    MissingTimestamps,
+    /// A setting is out of its range, e.g. a negative half life or a test ratio above 1.
+    InvalidConfig(String),
+}
+
+impl DatasetError {
+    pub(crate) fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        DatasetError::Io {
+            path: path.into(),
+            source,
+        }
+    }
+
+    pub(crate) fn parse(path: impl Into<PathBuf>, line: u64, reason: impl Into<String>) -> Self {
+        DatasetError::Parse {
+            path: path.into(),
+            line,
+            reason: reason.into(),
+        }
+    }
+
+    pub(crate) fn corrupted(path: impl Into<PathBuf>, reason: impl Into<String>) -> Self {
+        DatasetError::Corrupted {
+            path: path.into(),
+            reason: reason.into(),
+        }
+    }
+
+    pub(crate) fn schema(path: impl Into<PathBuf>, reason: impl Into<String>) -> Self {
+        DatasetError::Schema {
+            path: path.into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl Display for DatasetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DatasetError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            DatasetError::Parse { path, line, reason } => {
+                write!(f, "{}:{}: {}", path.display(), line, reason)
+            }
+            DatasetError::Schema { path, reason } => write!(f, "{}: {}", path.display(), reason),
+            DatasetError::Corrupted { path, reason } => {
+                write!(f, "{}: corrupted snapshot, {}", path.display(), reason)
+            }
+            DatasetError::MissingTimestamps => write!(f, "the dataset has no event timestamps"),
+            DatasetError::InvalidConfig(reason) => write!(f, "invalid config, {}", reason),
+        }
+    }
+}
+
+impl Error for DatasetError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DatasetError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Errors raised while training, querying or saving a
+/// [`SimilarityEngine`](super::similarity::SimilarityEngine).
+#[derive(Debug)]
+pub enum RecommenderError {
+    /// The engine was queried or evaluated before being trained.
+    NotTrained,
+    /// The user is not in the dataset the engine was trained on.
+    UnknownUser(String),
+    /// The item is not in the dataset the engine was trained on.
+    UnknownItem(String),
+    /// A model or dataset file could not be written or read.
+    Io { path: PathBuf, source: io::Error },
+    /// A model or dataset could not be encoded or decoded.
+    Serialization { path: PathBuf, reason: String },
+    /// The engine was set up or called with parameters it cannot work with, e.g. a test dataset
+    /// that does not share the indexes of the training dataset.
+    InvalidConfig(String),
+    /// Training produced non finite values at the given epoch, e.g. because the learning rate is
+    /// too high for the data.
+    NumericalDivergence { epoch: usize },
+}
+
+impl RecommenderError {
+    pub(crate) fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        RecommenderError::Io {
+            path: path.into(),
+            source,
+        }
+    }
+
+    pub(crate) fn serialization(path: impl Into<PathBuf>, reason: impl ToString) -> Self {
+        RecommenderError::Serialization {
+            path: path.into(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl Display for RecommenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecommenderError::NotTrained => write!(f, "the engine is not trained"),
+            RecommenderError::UnknownUser(user_id) => write!(f, "unknown user `{}`", user_id),
+            RecommenderError::UnknownItem(item_id) => write!(f, "unknown item `{}`", item_id),
+            RecommenderError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            RecommenderError::Serialization { path, reason } => {
+                write!(f, "{}: {}", path.display(), reason)
+            }
+            RecommenderError::InvalidConfig(reason) => write!(f, "invalid config, {}", reason),
+            RecommenderError::NumericalDivergence { epoch } => {
+                write!(f, "training diverged at epoch {}", epoch)
+            }
+        }
+    }
+}
+
+impl Error for RecommenderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RecommenderError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Lets engines loading their data with `?` report dataset failures as their own.
+impl From<DatasetError> for RecommenderError {
+    fn from(error: DatasetError) -> Self {
+        match error {
+            DatasetError::Io { path, source } => RecommenderError::Io { path, source },
+            DatasetError::Parse { path, line, reason } => {
+                RecommenderError::serialization(path, format!("line {}: {}", line, reason))
+            }
+            DatasetError::Schema { path, reason } | DatasetError::Corrupted { path, reason } => {
+                RecommenderError::Serialization { path, reason }
+            }
+            DatasetError::MissingTimestamps => {
+                RecommenderError::InvalidConfig(DatasetError::MissingTimestamps.to_string())
+            }
+            DatasetError::InvalidConfig(reason) => RecommenderError::InvalidConfig(reason),
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use super::dataset::Dataset;
+
+/// Thresholds on the number of interactions of users and items, applied by [`Dataset::filter`].
+///
+/// Interactions are the distinct (user, item) pairs of the user-item matrix. Users and items
+/// under the minimums are removed repeatedly until every remaining one satisfies them, which
+/// with equal minimums gives the k-core of the interaction graph. Users and items without any
+/// interaction are always removed, whatever the thresholds.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InteractionFilter {
+    min_user_interactions: usize,
+    min_item_interactions: usize,
+    max_user_interactions: Option<usize>,
+}
+
+impl InteractionFilter {
+    /// A filter without thresholds, which only drops the users and items without interactions,
+    /// e.g. the empty rows of a train split.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps the users and items with at least `k` interactions among each other.
+    pub fn k_core(k: usize) -> Self {
+        Self::new()
+            .with_min_user_interactions(k)
+            .with_min_item_interactions(k)
+    }
+
+    pub fn with_min_user_interactions(mut self, min: usize) -> Self {
+        self.min_user_interactions = min;
+        self
+    }
+
+    pub fn with_min_item_interactions(mut self, min: usize) -> Self {
+        self.min_item_interactions = min;
+        self
+    }
+
+    /// Caps users with more than `max` interactions, such as crawlers or shared accounts, to their
+    /// `max` most recent interactions, or to their `max` heaviest ones when the dataset has no
+    /// timestamps.
+    ///
+    /// The cap is applied to the unfiltered dataset, before the minimums are.
+    pub fn with_max_user_interactions(mut self, max: usize) -> Self {
+        self.max_user_interactions = Some(max);
+        self
+    }
+}
+
+/// What [`Dataset::filter`] removed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FilterReport {
+    pub users_dropped: usize,
+    pub items_dropped: usize,
+    pub interactions_dropped: usize,
+    /// Number of passes it took for the thresholds to hold.
+    pub iterations: usize,
+}
+
+impl Dataset {
+    /// Removes the users and items that do not meet the thresholds of `filter`, and re-indexes the
+    /// remaining ones compactly, keeping their relative order.
+    pub fn filter(&self, filter: &InteractionFilter) -> (Dataset, FilterReport) {
+        let (user_size, item_size) = self.cui.shape();
+
+        let capped;
+        let source = match filter.max_user_interactions {
+            Some(max) => {
+                capped = self.cap_users(max);
+                &capped
+            }
+            None => self,
+        };
+
+        let mut keep_users = vec![true; user_size];
+        let mut keep_items = vec![true; item_size];
+
+        let mut iterations = 0;
+        loop {
+            iterations += 1;
+
+            let mut user_counts = vec![0usize; user_size];
+            let mut item_counts = vec![0usize; item_size];
+            for (_, (u, i)) in source.cui.iter() {
+                if keep_users[u] && keep_items[i] {
+                    user_counts[u] += 1;
+                    item_counts[i] += 1;
+                }
+            }
+
+            let mut changed = false;
+            for (keep, &count) in keep_users.iter_mut().zip(user_counts.iter()) {
+                if *keep && (count == 0 || count < filter.min_user_interactions) {
+                    *keep = false;
+                    changed = true;
+                }
+            }
+            for (keep, &count) in keep_items.iter_mut().zip(item_counts.iter()) {
+                if *keep && (count == 0 || count < filter.min_item_interactions) {
+                    *keep = false;
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        let filtered = source.retain(&keep_users, &keep_items);
+
+        let report = FilterReport {
+            users_dropped: user_size - filtered.user_idx.size(),
+            items_dropped: item_size - filtered.item_idx.size(),
+            interactions_dropped: self.cui.nnz() - filtered.cui.nnz(),
+            iterations,
+        };
+
+        (filtered, report)
+    }
+
+    /// Keeps at most `max` interactions per user, the most recent ones first, then the heaviest.
+    fn cap_users(&self, max: usize) -> Dataset {
+        let mut dropped = Vec::with_capacity(self.cui.nnz());
+        for (user, row) in self.cui.outer_iterator().enumerate() {
+            let mut ranked = row.iter().enumerate().collect::<Vec<_>>();
+            ranked.sort_by(|(_, (a, &a_value)), (_, (b, &b_value))| {
+                self.last_seen_millis(user, *b)
+                    .cmp(&self.last_seen_millis(user, *a))
+                    .then(b_value.total_cmp(&a_value))
+                    .then(a.cmp(b))
+            });
+
+            let mut row_dropped = vec![false; ranked.len()];
+            for &(position, _) in ranked.iter().skip(max) {
+                row_dropped[position] = true;
+            }
+            dropped.extend(row_dropped);
+        }
+
+        self.partition(&dropped).0
+    }
+}
+
+#[cfg(test)]
+mod filter_test {
+    use chrono::{TimeZone, Utc};
+
+    use crate::core::{dataset::Dataset, model::Event};
+
+    use super::{FilterReport, InteractionFilter};
+
+    fn dataset(pairs: &[(&str, &str)]) -> Dataset {
+        pairs
+            .iter()
+            .map(|(u, i)| Event::new(u.to_string(), i.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn should_find_the_k_core() {
+        // dropping u-3 leaves p-4 with a single user, which in turn drops u-2 and then p-3.
+        let dataset = dataset(&[
+            ("u-1", "p-1"),
+            ("u-1", "p-2"),
+            ("u-1", "p-3"),
+            ("u-2", "p-3"),
+            ("u-2", "p-4"),
+            ("u-3", "p-4"),
+            ("u-4", "p-1"),
+            ("u-4", "p-2"),
+        ]);
+
+        let (filtered, report) = dataset.filter(&InteractionFilter::k_core(2));
+
+        assert_eq!(
+            FilterReport {
+                users_dropped: 2,
+                items_dropped: 2,
+                interactions_dropped: 4,
+                iterations: 5,
+            },
+            report
+        );
+        assert_eq!((2, 2), filtered.cui.shape());
+        assert_eq!(Some("u-4"), filtered.user_idx.get_item(1));
+        assert_eq!(Some("p-2"), filtered.item_idx.get_item(1));
+        assert_eq!((2, 2), filtered.ciu().shape());
+    }
+
+    #[test]
+    fn should_cap_heavy_users() {
+        let dataset = dataset(&[
+            ("u-1", "p-1"),
+            ("u-1", "p-2"),
+            ("u-1", "p-3"),
+            ("u-2", "p-1"),
+        ]);
+
+        let (filtered, report) =
+            dataset.filter(&InteractionFilter::new().with_max_user_interactions(2));
+
+        // without timestamps nor weights, the first interactions of u-1 are kept.
+        assert_eq!(0, report.users_dropped);
+        assert_eq!(1, report.items_dropped);
+        assert_eq!(1, report.interactions_dropped);
+        assert_eq!(3, filtered.cui.nnz());
+        assert_eq!(None, filtered.item_idx.lookup("p-3"));
+    }
+
+    #[test]
+    fn should_keep_the_most_recent_interactions_of_heavy_users() {
+        let at = |secs| Utc.timestamp_opt(secs, 0).unwrap();
+        let dataset = Dataset::from_events(vec![
+            Event::new("u-1".to_string(), "p-1".to_string()).with_timestamp(at(30)),
+            Event::new("u-1".to_string(), "p-2".to_string())
+                .with_timestamp(at(10))
+                .with_weight(5.0),
+            Event::new("u-1".to_string(), "p-3".to_string()).with_timestamp(at(20)),
+            Event::new("u-2".to_string(), "p-2".to_string()).with_timestamp(at(10)),
+        ]);
+
+        let (filtered, report) =
+            dataset.filter(&InteractionFilter::new().with_max_user_interactions(2));
+
+        assert_eq!(0, report.users_dropped);
+        assert_eq!(0, report.items_dropped);
+        assert_eq!(1, report.interactions_dropped);
+        let u_1 = filtered.user_idx.lookup("u-1").unwrap();
+        let p_2 = filtered.item_idx.lookup("p-2").unwrap();
+        let p_3 = filtered.item_idx.lookup("p-3").unwrap();
+        assert_eq!(2, filtered.cui.outer_view(u_1).unwrap().nnz());
+        assert_eq!(None, filtered.cui.get(u_1, p_2));
+        assert_eq!(Some(at(20)), filtered.last_seen(u_1, p_3));
+    }
+}
@@ -1,63 +1,389 @@
-use std::collections::{
-    hash_map::{Entry, OccupiedEntry},
-    HashMap,
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    fmt::Debug,
+    hash::{BuildHasher, Hash},
+    sync::Arc,
 };
 
+use hashbrown::HashTable;
+use ndarray::{Array2, ArrayView2, Axis};
+
+use super::error::DatasetError;
+
+/// A type that can key an [`ItemIndex`].
+///
+/// Lookups take the [`Borrowed`](Self::Borrowed) form of the key, `str` for `String` keys, so
+/// that querying an index never allocates. Implement it for your own id types with `Vec<Self>`
+/// as the store:
+///
+/// ```
+/// use rs_mender::core::item_index::{IndexKey, ItemIndex};
+///
+/// #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+/// struct SessionId(u64);
+///
+/// impl IndexKey for SessionId {
+///     type Borrowed = SessionId;
+///     type Store = Vec<SessionId>;
+/// }
+///
+/// let index = [SessionId(7), SessionId(3)].into_iter().collect::<ItemIndex<SessionId>>();
+/// assert_eq!(Some(1), index.lookup(&SessionId(3)));
+/// ```
+pub trait IndexKey: Borrow<Self::Borrowed> + Hash + Eq + Sized {
+    type Borrowed: ?Sized + Hash + Eq;
+    /// Keeps the keys of an index in index order.
+    type Store: KeyStore<Self::Borrowed>;
+
+    /// Adds the key to the store, copying its borrowed form unless the store can keep the key
+    /// itself, e.g. to share an interned `Arc<str>`.
+    fn push_to(self, store: &mut Self::Store) {
+        store.push(self.borrow());
+    }
+}
+
+/// Storage of the keys of an [`ItemIndex`], in index order.
+pub trait KeyStore<B: ?Sized>: Clone + Debug + Default {
+    fn push(&mut self, key: &B);
+
+    fn get(&self, idx: usize) -> Option<&B>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<B> KeyStore<B> for Vec<B::Owned>
+where
+    B: ?Sized + ToOwned,
+    B::Owned: Clone + Debug,
+{
+    fn push(&mut self, key: &B) {
+        Vec::push(self, key.to_owned());
+    }
+
+    fn get(&self, idx: usize) -> Option<&B> {
+        self.as_slice().get(idx).map(Borrow::borrow)
+    }
+
+    fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+}
+
+/// Strings laid end to end in a single buffer, the store of `String` keys.
+///
+/// A key costs its bytes and an offset instead of a `String` of its own, so millions of short
+/// ids take about half the memory.
+#[derive(Clone, Debug, Default)]
+pub struct StringArena {
+    bytes: String,
+    ends: Vec<usize>,
+}
+
+impl KeyStore<str> for StringArena {
+    fn push(&mut self, key: &str) {
+        self.bytes.push_str(key);
+        self.ends.push(self.bytes.len());
+    }
+
+    fn get(&self, idx: usize) -> Option<&str> {
+        let end = *self.ends.get(idx)?;
+        let start = if idx == 0 { 0 } else { self.ends[idx - 1] };
+        Some(&self.bytes[start..end])
+    }
+
+    fn len(&self) -> usize {
+        self.ends.len()
+    }
+}
+
+impl IndexKey for String {
+    type Borrowed = str;
+    type Store = StringArena;
+}
+
+/// Shared strings, the store of `Arc<str>` keys, holding the very `Arc`s the index was given.
+#[derive(Clone, Debug, Default)]
+pub struct InternedStrings {
+    keys: Vec<Arc<str>>,
+}
+
+impl KeyStore<str> for InternedStrings {
+    fn push(&mut self, key: &str) {
+        self.keys.push(Arc::from(key));
+    }
+
+    fn get(&self, idx: usize) -> Option<&str> {
+        self.keys.as_slice().get(idx).map(|key| &**key)
+    }
+
+    fn len(&self) -> usize {
+        self.keys.as_slice().len()
+    }
+}
+
+/// Interned ids, queried with a `&str` like `String` keys.
+impl IndexKey for Arc<str> {
+    type Borrowed = str;
+    type Store = InternedStrings;
+
+    fn push_to(self, store: &mut Self::Store) {
+        store.keys.push(self);
+    }
+}
+
+macro_rules! owned_index_keys {
+    ($($key:ty),*) => {
+        $(
+            impl IndexKey for $key {
+                type Borrowed = $key;
+                type Store = Vec<$key>;
+            }
+        )*
+    };
+}
+
+owned_index_keys!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, char);
+
+impl<const N: usize> IndexKey for [u8; N] {
+    type Borrowed = [u8; N];
+    type Store = Vec<[u8; N]>;
+}
+
+#[cfg(feature = "uuid")]
+owned_index_keys!(uuid::Uuid);
+
+/// Two-way mapping between ids and the dense indexes of the rows or columns of a matrix.
+///
+/// Every key is stored once, in the [`KeyStore`] of its type, the hash table only holding
+/// indexes into it.
+///
+/// An index is frozen once it belongs to a [`Dataset`](super::dataset::Dataset), its size then
+/// matching the matrices. Use [`lookup`](Self::lookup) to query it, ids unknown to a frozen
+/// index are never added.
 #[derive(Clone, Debug)]
-pub struct ItemIndex {
-    item_to_index: HashMap<String, usize>,
-    index_to_item: Vec<String>,
+pub struct ItemIndex<K: IndexKey = String> {
+    keys: K::Store,
+    table: HashTable<usize>,
+    hasher: RandomState,
+    frozen: bool,
 }
 
-impl ItemIndex {
+impl<K: IndexKey> ItemIndex<K> {
     pub fn new() -> Self {
         Self {
-            item_to_index: HashMap::new(),
-            index_to_item: Vec::new(),
+            keys: K::Store::default(),
+            table: HashTable::new(),
+            hasher: RandomState::new(),
+            frozen: false,
         }
     }
 
-    pub fn get_idx(&mut self, item: String) -> usize {
-        match self.item_to_index.entry(item) {
-            Entry::Occupied(e) => *e.get(),
-            Entry::Vacant(v) => {
-                self.index_to_item.push(v.key().clone());
+    /// Index of the item, indexing it first when it is unknown. `None` when the item is unknown
+    /// and the index is [frozen](Self::freeze), which leaves it as is.
+    pub fn get_idx(&mut self, item: K) -> Option<usize> {
+        let hash = self.hasher.hash_one(item.borrow());
+        match self.find(hash, item.borrow()) {
+            Some(idx) => Some(idx),
+            None => self.insert(hash, |keys| item.push_to(keys)),
+        }
+    }
 
-                let inserted_idx = v.insert(self.index_to_item.len() - 1);
-                *inserted_idx
-            }
+    /// Same as [`get_idx`](Self::get_idx), only copying the key when it is not indexed yet.
+    pub fn get_or_insert(&mut self, item: &K::Borrowed) -> Option<usize> {
+        let hash = self.hasher.hash_one(item);
+        match self.find(hash, item) {
+            Some(idx) => Some(idx),
+            None => self.insert(hash, |keys| keys.push(item)),
         }
     }
 
-    pub fn get_item(&self, idx: usize) -> String {
-        self.index_to_item.get(idx).unwrap().clone()
+    fn find(&self, hash: u64, item: &K::Borrowed) -> Option<usize> {
+        self.table
+            .find(hash, |&idx| self.keys.get(idx) == Some(item))
+            .copied()
     }
 
-    pub fn has_item(&self, item: String) -> bool {
-        self.item_to_index.contains_key(&item)
+    fn insert(&mut self, hash: u64, push: impl FnOnce(&mut K::Store)) -> Option<usize> {
+        if self.frozen {
+            return None;
+        }
+
+        let idx = self.keys.len();
+        push(&mut self.keys);
+
+        let Self {
+            keys,
+            table,
+            hasher,
+            ..
+        } = self;
+        table.insert_unique(hash, idx, |&i| hasher.hash_one(keys.get(i).unwrap()));
+        Some(idx)
+    }
+
+    /// Index of an item, `None` when it is unknown. Never changes the index.
+    pub fn lookup(&self, item: &K::Borrowed) -> Option<usize> {
+        self.find(self.hasher.hash_one(item), item)
+    }
+
+    /// Item at an index, `None` past the end of the index.
+    pub fn get_item(&self, idx: usize) -> Option<&K::Borrowed> {
+        self.keys.get(idx)
+    }
+
+    pub fn has_item(&self, item: &K::Borrowed) -> bool {
+        self.lookup(item).is_some()
     }
 
     pub fn size(&self) -> usize {
-        self.index_to_item.len()
+        self.keys.len()
+    }
+
+    /// Makes the index read-only, see [`get_idx`](Self::get_idx).
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Items in the order of their indexes.
+    pub fn items(&self) -> impl ExactSizeIterator<Item = &K::Borrowed> + '_ {
+        (0..self.size()).map(|idx| self.keys.get(idx).unwrap())
+    }
+}
+
+impl<K: IndexKey> ItemIndex<K> {
+    /// Where each item of this index is in `other`, e.g. the index of a retrained dataset.
+    pub fn remap_to(&self, other: &ItemIndex<K>) -> IndexMapping {
+        IndexMapping {
+            targets: self.items().map(|item| other.lookup(item)).collect(),
+            target_size: other.size(),
+        }
+    }
+
+    /// An index holding the items of both, those of this index first and at the same indexes, so
+    /// that anything built on it stays valid. The mapping tells where the items of `other` went.
+    pub fn merge(&self, other: &ItemIndex<K>) -> (ItemIndex<K>, IndexMapping) {
+        let mut merged = ItemIndex::new();
+        for item in self.items().chain(other.items()) {
+            merged.get_or_insert(item);
+        }
+
+        let mapping = other.remap_to(&merged);
+        (merged, mapping)
+    }
+}
+
+/// Where the entries of one [`ItemIndex`] are in another one, see [`ItemIndex::remap_to`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexMapping {
+    targets: Vec<Option<usize>>,
+    target_size: usize,
+}
+
+impl IndexMapping {
+    /// Index in the target of the entry at `idx`, `None` when the target does not have it.
+    pub fn get(&self, idx: usize) -> Option<usize> {
+        self.targets.get(idx).copied().flatten()
+    }
+
+    /// Target indexes in the order of the source index.
+    pub fn as_slice(&self) -> &[Option<usize>] {
+        &self.targets
+    }
+
+    /// Number of entries of the source index.
+    pub fn len(&self) -> usize {
+        self.targets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    /// Number of entries of the target index.
+    pub fn target_size(&self) -> usize {
+        self.target_size
+    }
+
+    /// Source indexes of the entries the target does not have.
+    pub fn unmatched(&self) -> impl Iterator<Item = usize> + '_ {
+        self.targets
+            .iter()
+            .enumerate()
+            .filter(|(_, target)| target.is_none())
+            .map(|(idx, _)| idx)
+    }
+
+    /// Moves the rows of a matrix in source order, such as the factors of a model, to target
+    /// order. Rows of entries new to the target are left at zero, rows of entries the target does
+    /// not have are dropped.
+    ///
+    /// Fails with [`DatasetError::InvalidConfig`] when the matrix does not have a row per entry
+    /// of the source index.
+    pub fn reorder_rows(&self, matrix: ArrayView2<f64>) -> Result<Array2<f64>, DatasetError> {
+        if matrix.nrows() != self.len() {
+            return Err(DatasetError::InvalidConfig(format!(
+                "matrix of {} rows should have a row per entry of the source index, {} entries",
+                matrix.nrows(),
+                self.len()
+            )));
+        }
+
+        let mut reordered = Array2::zeros((self.target_size, matrix.ncols()));
+        for (source, row) in matrix.axis_iter(Axis(0)).enumerate() {
+            if let Some(target) = self.get(source) {
+                reordered.row_mut(target).assign(&row);
+            }
+        }
+        Ok(reordered)
+    }
+}
+
+impl<K: IndexKey> Default for ItemIndex<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: IndexKey> FromIterator<K> for ItemIndex<K> {
+    /// Indexes the items in iteration order, repeated items keep their first index.
+    fn from_iter<T: IntoIterator<Item = K>>(iter: T) -> Self {
+        let mut item_idx = ItemIndex::new();
+        iter.into_iter().for_each(|item| {
+            item_idx.get_idx(item);
+        });
+        item_idx
     }
 }
 
 #[cfg(test)]
 mod test {
+    use std::{collections::HashMap, mem::size_of, sync::Arc};
 
     use itertools::Itertools;
+    use ndarray::array;
+
+    use crate::core::error::DatasetError;
 
     use super::ItemIndex;
 
     #[test]
     pub fn should_give_indexes() {
-        let v = vec!["a", "b", "c", "d", "a", "b", "a", "d"];
+        let v = ["a", "b", "c", "d", "a", "b", "a", "d"];
 
         let mut item_idx = ItemIndex::new();
 
         let ids = v
             .iter()
-            .map(|&i| item_idx.get_idx(i.to_string()))
+            .map(|&i| item_idx.get_idx(i.to_string()).unwrap())
             .collect::<Vec<_>>();
 
         assert_eq!(
@@ -67,18 +393,153 @@ mod test {
         println!("{:?}", ids);
 
         let idx = item_idx.get_idx("c".to_string());
-        assert_eq!(2, idx);
+        assert_eq!(Some(2), idx);
 
         let idx = item_idx.get_idx("a".to_string());
-        assert_eq!(0, idx);
+        assert_eq!(Some(0), idx);
 
         let idx = item_idx.get_idx("d".to_string());
-        assert_eq!(3, idx);
+        assert_eq!(Some(3), idx);
 
-        let exists = item_idx.has_item("a".to_string());
+        let exists = item_idx.has_item("a");
         assert!(exists);
 
-        let exists = item_idx.has_item("x".to_string());
+        let exists = item_idx.has_item("x");
         assert!(!exists);
+
+        assert_eq!(Some("c"), item_idx.get_item(2));
+        assert_eq!(None, item_idx.get_item(4));
+    }
+
+    #[test]
+    pub fn should_not_grow_a_frozen_index() {
+        let mut item_idx = ["a", "b"]
+            .map(String::from)
+            .into_iter()
+            .collect::<ItemIndex>();
+        item_idx.freeze();
+
+        assert_eq!(Some(1), item_idx.lookup("b"));
+        assert_eq!(None, item_idx.lookup("x"));
+        assert_eq!(Some(1), item_idx.get_idx("b".to_string()));
+        assert_eq!(2, item_idx.size());
+    }
+
+    #[test]
+    pub fn should_index_integer_keys() {
+        let mut item_idx = ItemIndex::<u64>::new();
+
+        assert_eq!(Some(0), item_idx.get_idx(42));
+        assert_eq!(Some(1), item_idx.get_or_insert(&7));
+        assert_eq!(Some(0), item_idx.get_idx(42));
+
+        assert_eq!(Some(1), item_idx.lookup(&7));
+        assert_eq!(Some(&42), item_idx.get_item(0));
+        assert_eq!(vec![&42, &7], item_idx.items().collect_vec());
+    }
+
+    #[test]
+    pub fn should_store_string_keys_end_to_end() {
+        let mut item_idx = ItemIndex::<String>::new();
+        for item in ["", "ab", "c", "ab", "déjà"] {
+            item_idx.get_or_insert(item);
+        }
+
+        assert_eq!(4, item_idx.size());
+        assert_eq!(vec!["", "ab", "c", "déjà"], item_idx.items().collect_vec());
+        assert_eq!(Some(3), item_idx.lookup("déjà"));
+        assert_eq!(Some(0), item_idx.lookup(""));
+    }
+
+    #[test]
+    pub fn should_look_up_interned_keys_by_str() {
+        let id: Arc<str> = Arc::from("user-1");
+        let mut item_idx = ItemIndex::<Arc<str>>::new();
+
+        assert_eq!(Some(0), item_idx.get_idx(id.clone()));
+        assert_eq!(Some(1), item_idx.get_or_insert("user-2"));
+
+        assert_eq!(Some(0), item_idx.lookup("user-1"));
+        assert_eq!(Some("user-2"), item_idx.get_item(1));
+        // the index keeps the given key instead of a copy.
+        assert_eq!(2, Arc::strong_count(&id));
+    }
+
+    #[test]
+    pub fn should_take_half_the_memory_of_the_former_layout() {
+        let ids = (0..100_000).map(|i| format!("user-{}", i)).collect_vec();
+        // buckets of a hash table, plus a control byte each.
+        let table_bytes = |capacity: usize, entry: usize| capacity * (entry + 1);
+
+        let item_idx = ids.iter().cloned().collect::<ItemIndex>();
+        let arena_bytes = item_idx.keys.bytes.capacity()
+            + item_idx.keys.ends.capacity() * size_of::<usize>()
+            + table_bytes(item_idx.table.capacity(), size_of::<usize>());
+
+        // every id was kept twice, as a key of the map and in the vector of items.
+        let mut item_to_index = HashMap::new();
+        let mut index_to_item = Vec::new();
+        for id in &ids {
+            item_to_index.insert(id.clone(), index_to_item.len());
+            index_to_item.push(id.clone());
+        }
+        let former_bytes = table_bytes(item_to_index.capacity(), size_of::<(String, usize)>())
+            + index_to_item.capacity() * size_of::<String>()
+            + item_to_index
+                .keys()
+                .chain(&index_to_item)
+                .map(String::capacity)
+                .sum::<usize>();
+
+        assert!(
+            arena_bytes * 2 <= former_bytes,
+            "{} bytes instead of {}",
+            arena_bytes,
+            former_bytes
+        );
+    }
+
+    #[test]
+    pub fn should_not_add_to_a_frozen_index() {
+        let mut item_idx = ItemIndex::new();
+        item_idx.freeze();
+
+        assert_eq!(None, item_idx.get_idx("x".to_string()));
+        assert_eq!(None, item_idx.get_or_insert("y"));
+        assert_eq!(0, item_idx.size());
+    }
+
+    #[test]
+    pub fn should_map_between_indexes() {
+        let previous = ["a", "b", "c"]
+            .map(String::from)
+            .into_iter()
+            .collect::<ItemIndex>();
+        let retrained = ["c", "d", "a"]
+            .map(String::from)
+            .into_iter()
+            .collect::<ItemIndex>();
+
+        let mapping = retrained.remap_to(&previous);
+        assert_eq!(&[Some(2), None, Some(0)], mapping.as_slice());
+        assert_eq!(vec![1], mapping.unmatched().collect_vec());
+
+        let (merged, mapping) = previous.merge(&retrained);
+        assert_eq!(vec!["a", "b", "c", "d"], merged.items().collect_vec());
+        assert_eq!(&[Some(2), Some(3), Some(0)], mapping.as_slice());
+        assert_eq!(4, mapping.target_size());
+
+        let factors = array![[1.0, 1.0], [2.0, 2.0], [3.0, 3.0]];
+        let reordered = mapping.reorder_rows(factors.view()).unwrap();
+        assert_eq!(
+            array![[3.0, 3.0], [0.0, 0.0], [1.0, 1.0], [2.0, 2.0]],
+            reordered
+        );
+
+        let missing_a_row = array![[1.0, 1.0], [2.0, 2.0]];
+        assert!(matches!(
+            mapping.reorder_rows(missing_a_row.view()),
+            Err(DatasetError::InvalidConfig(reason)) if reason.contains("2 rows")
+        ));
     }
 }
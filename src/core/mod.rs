@@ -1,8 +1,19 @@
 use std::collections::HashMap;
 
+pub mod catalog;
 pub mod dataset;
+pub mod erasure;
+pub mod error;
+pub mod filter;
 pub mod item_index;
 pub mod model;
+pub mod sampling;
+pub mod schema;
+pub mod session;
 pub mod similarity;
+pub mod snapshot;
+pub mod split;
+pub mod stats;
+pub mod synthetic;
 
 pub type DetailedRecommendations = HashMap<String, Vec<(String, f64)>>;
@@ -1,12 +1,87 @@
-#[derive(Clone, Debug)]
+use std::{convert::Infallible, fmt::Display, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Kind of interaction an [`Event`] records.
+///
+/// Each kind carries a different strength of signal, see
+/// [`EventWeights`](super::dataset::EventWeights) for how they are weighted into a dataset.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EventType {
+    /// An interaction without a more specific type.
+    #[default]
+    Interaction,
+    View,
+    AddToCart,
+    Purchase,
+    Rating,
+    Other(String),
+}
+
+impl FromStr for EventType {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let event_type = match s.trim().to_lowercase().replace(['-', ' '], "_").as_str() {
+            "interaction" => EventType::Interaction,
+            "view" | "click" => EventType::View,
+            "add_to_cart" | "cart" => EventType::AddToCart,
+            "purchase" | "buy" => EventType::Purchase,
+            "rating" | "rate" => EventType::Rating,
+            _ => EventType::Other(s.trim().to_string()),
+        };
+        Ok(event_type)
+    }
+}
+
+impl Display for EventType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EventType::Interaction => write!(f, "interaction"),
+            EventType::View => write!(f, "view"),
+            EventType::AddToCart => write!(f, "add_to_cart"),
+            EventType::Purchase => write!(f, "purchase"),
+            EventType::Rating => write!(f, "rating"),
+            EventType::Other(other) => write!(f, "{}", other),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Event {
     user_id: String,
     target_id: String,
+    event_type: EventType,
+    weight: f64,
+    timestamp: Option<DateTime<Utc>>,
 }
 
 impl Event {
     pub fn new(user_id: String, target_id: String) -> Self {
-        Self { user_id, target_id }
+        Self {
+            user_id,
+            target_id,
+            event_type: EventType::default(),
+            weight: 1.0,
+            timestamp: None,
+        }
+    }
+
+    pub fn with_event_type(mut self, event_type: EventType) -> Self {
+        self.event_type = event_type;
+        self
+    }
+
+    /// Sets the weight of the event, e.g. the value of a rating. Defaults to `1.0`.
+    pub fn with_weight(mut self, weight: f64) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    pub fn with_timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.timestamp = Some(timestamp);
+        self
     }
 
     pub fn user_id(&self) -> &str {
@@ -16,16 +91,118 @@ impl Event {
     pub fn target_id(&self) -> &str {
         self.target_id.as_ref()
     }
+
+    pub fn event_type(&self) -> &EventType {
+        &self.event_type
+    }
+
+    pub fn weight(&self) -> f64 {
+        self.weight
+    }
+
+    /// When the event happened, if the source recorded it.
+    pub fn timestamp(&self) -> Option<DateTime<Utc>> {
+        self.timestamp
+    }
+}
+
+/// An item of a [`RecommendationResponse`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecommendedItem {
+    item_id: String,
+    score: f64,
+    rank: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
 }
 
-#[derive(Debug)]
+impl RecommendedItem {
+    pub fn item_id(&self) -> &str {
+        &self.item_id
+    }
+
+    /// Score the engine ranked the item by, only comparable within the same model.
+    pub fn score(&self) -> f64 {
+        self.score
+    }
+
+    /// Position of the item in the response, starting at 1.
+    pub fn rank(&self) -> usize {
+        self.rank
+    }
+
+    /// Why the item was recommended, when the engine can tell.
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+}
+
+/// Ranked items returned by a [`SimilarityEngine`](super::similarity::SimilarityEngine), along
+/// with the engine and the version of the model that produced them.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct RecommendationResponse {
-    recommendations: Vec<String>,
+    engine: String,
+    model_version: String,
+    recommendations: Vec<RecommendedItem>,
 }
 
 impl RecommendationResponse {
-    /// Creates a new [`RecommendationResponse`].
-    pub fn new(recommendations: Vec<String>) -> Self {
-        Self { recommendations }
+    /// Creates a new [`RecommendationResponse`] of the `(item id, score)` pairs, ranked in the
+    /// order they are given.
+    pub fn new<I>(engine: impl Into<String>, model_version: impl Into<String>, scored: I) -> Self
+    where
+        I: IntoIterator<Item = (String, f64)>,
+    {
+        let recommendations = scored
+            .into_iter()
+            .enumerate()
+            .map(|(idx, (item_id, score))| RecommendedItem {
+                item_id,
+                score,
+                rank: idx + 1,
+                reason: None,
+            })
+            .collect();
+
+        Self {
+            engine: engine.into(),
+            model_version: model_version.into(),
+            recommendations,
+        }
+    }
+
+    /// Gives every item the same reason, e.g. the item they are similar to.
+    pub fn with_reason(mut self, reason: impl Into<String>) -> Self {
+        let reason = reason.into();
+        for item in self.recommendations.iter_mut() {
+            item.reason = Some(reason.clone());
+        }
+        self
+    }
+
+    pub fn engine(&self) -> &str {
+        &self.engine
+    }
+
+    pub fn model_version(&self) -> &str {
+        &self.model_version
+    }
+
+    /// Recommended items, best first.
+    pub fn recommendations(&self) -> &[RecommendedItem] {
+        self.recommendations.as_ref()
+    }
+
+    /// Ids of the recommended items, best first.
+    pub fn item_ids(&self) -> impl Iterator<Item = &str> + '_ {
+        self.recommendations.iter().map(RecommendedItem::item_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.recommendations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recommendations.is_empty()
     }
 }
@@ -0,0 +1,282 @@
+use std::collections::HashSet;
+
+use ndarray_rand::rand::{
+    distributions::{Distribution, WeightedIndex},
+    rngs::StdRng,
+    seq::SliceRandom,
+    Rng, SeedableRng,
+};
+
+use super::{dataset::Dataset, error::DatasetError};
+
+/// Rejected draws after which a sampler stops guessing and picks among the remaining candidates
+/// directly, for users who interacted with most of the items.
+const MAX_REJECTIONS: usize = 32;
+
+/// How a [`NegativeSampler`] picks items.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NegativeDistribution {
+    /// Every item the user did not interact with is equally likely.
+    Uniform,
+    /// Items are picked proportionally to their number of users raised to `exponent`, `0.75` being
+    /// a common choice. Items without users are never picked.
+    Popularity { exponent: f64 },
+}
+
+/// One sampled negative of a positive interaction, see
+/// [`NegativeSampler::sample_interactions`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NegativeSample {
+    pub user_idx: usize,
+    pub positive_idx: usize,
+    pub negative_idx: usize,
+}
+
+/// Draws items a user did not interact with, such as the negatives of ranking losses or of AUC.
+///
+/// Items are drawn from the whole catalog and rejected when they are positives of the user,
+/// which only costs a binary search in the user's row of `cui`. The same dataset, distribution
+/// and seed always give the same samples.
+pub struct NegativeSampler<'a> {
+    dataset: &'a Dataset,
+    weights: Vec<f64>,
+    distribution: Option<WeightedIndex<f64>>,
+    rng: StdRng,
+}
+
+impl<'a> NegativeSampler<'a> {
+    /// Fails with [`DatasetError::InvalidConfig`] on a negative popularity exponent.
+    pub fn new(
+        dataset: &'a Dataset,
+        distribution: NegativeDistribution,
+        seed: u64,
+    ) -> Result<Self, DatasetError> {
+        let items = dataset.cui.cols();
+        let weights = match distribution {
+            NegativeDistribution::Uniform => vec![1.0; items],
+            NegativeDistribution::Popularity { exponent } => {
+                if exponent.is_nan() || exponent < 0.0 {
+                    return Err(DatasetError::InvalidConfig(format!(
+                        "popularity exponent should not be negative, got {}",
+                        exponent
+                    )));
+                }
+                (0..items)
+                    .map(|item_idx| match dataset.ciu().outer_view(item_idx) {
+                        Some(users) if users.nnz() > 0 => (users.nnz() as f64).powf(exponent),
+                        _ => 0.0,
+                    })
+                    .collect()
+            }
+        };
+
+        Ok(Self {
+            dataset,
+            // fails when no item can be picked, the sampler then never returns anything.
+            distribution: WeightedIndex::new(&weights).ok(),
+            weights,
+            rng: StdRng::seed_from_u64(seed),
+        })
+    }
+
+    fn is_positive(&self, user_idx: usize, item_idx: usize) -> bool {
+        self.dataset
+            .cui
+            .outer_view(user_idx)
+            .is_some_and(|row| row.get(item_idx).is_some())
+    }
+
+    /// A single negative of the user, `None` when every item the distribution can pick is a
+    /// positive of the user.
+    pub fn sample(&mut self, user_idx: usize) -> Option<usize> {
+        self.sample_for_user(user_idx, 1).pop()
+    }
+
+    /// Up to `n` distinct negatives of the user, fewer when the user does not have that many.
+    pub fn sample_for_user(&mut self, user_idx: usize, n: usize) -> Vec<usize> {
+        let Some(distribution) = &self.distribution else {
+            return Vec::new();
+        };
+
+        let mut picked = Vec::with_capacity(n);
+        let mut seen = HashSet::new();
+        let mut rejections = 0;
+        while picked.len() < n && rejections < MAX_REJECTIONS {
+            let item_idx = distribution.sample(&mut self.rng);
+            if !self.is_positive(user_idx, item_idx) && seen.insert(item_idx) {
+                picked.push(item_idx);
+            } else {
+                rejections += 1;
+            }
+        }
+
+        if picked.len() < n {
+            let remaining = n - picked.len();
+            let rest = self.pick_among_candidates(user_idx, &seen, remaining);
+            picked.extend(rest);
+        }
+
+        picked
+    }
+
+    /// Picks without replacement among the items that are neither positives nor already picked,
+    /// using weighted random keys `u^(1 / w)` so that the popularity distribution is kept.
+    fn pick_among_candidates(
+        &mut self,
+        user_idx: usize,
+        picked: &HashSet<usize>,
+        n: usize,
+    ) -> Vec<usize> {
+        let candidates = (0..self.weights.len())
+            .filter(|&i| self.weights[i] > 0.0)
+            .filter(|i| !picked.contains(i) && !self.is_positive(user_idx, *i))
+            .collect::<Vec<_>>();
+
+        let mut candidates = candidates
+            .into_iter()
+            .map(|i| (self.rng.gen::<f64>().powf(1.0 / self.weights[i]), i))
+            .collect::<Vec<_>>();
+
+        candidates.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        candidates.truncate(n);
+        candidates.into_iter().map(|(_, i)| i).collect()
+    }
+
+    /// `per_positive` negatives for every interaction of `cui`, in matrix order, e.g. the
+    /// (user, positive, negative) triples of a BPR epoch. Users without negatives are skipped.
+    pub fn sample_interactions(&mut self, per_positive: usize) -> Vec<NegativeSample> {
+        let dataset = self.dataset;
+        let mut samples = Vec::with_capacity(dataset.cui.nnz() * per_positive);
+        for (_, (user_idx, positive_idx)) in dataset.cui.iter() {
+            for negative_idx in self.sample_for_user(user_idx, per_positive) {
+                samples.push(NegativeSample {
+                    user_idx,
+                    positive_idx,
+                    negative_idx,
+                });
+            }
+        }
+        samples
+    }
+
+    /// Up to `n` distinct negatives for each of the users, in the order of the users.
+    pub fn sample_users(&mut self, user_indexes: &[usize], n: usize) -> Vec<Vec<usize>> {
+        user_indexes
+            .iter()
+            .map(|&user_idx| self.sample_for_user(user_idx, n))
+            .collect()
+    }
+
+    /// Shuffles the samples in place with the sampler's random generator, e.g. between epochs.
+    pub fn shuffle<T>(&mut self, samples: &mut [T]) {
+        samples.shuffle(&mut self.rng);
+    }
+}
+
+impl Dataset {
+    /// A seeded [`NegativeSampler`] over the interactions of this dataset, see
+    /// [`NegativeSampler::new`].
+    pub fn negative_sampler(
+        &self,
+        distribution: NegativeDistribution,
+        seed: u64,
+    ) -> Result<NegativeSampler<'_>, DatasetError> {
+        NegativeSampler::new(self, distribution, seed)
+    }
+}
+
+#[cfg(test)]
+mod sampling_test {
+    use crate::core::{error::DatasetError, model::Event, synthetic::SyntheticConfig};
+
+    use super::{Dataset, NegativeDistribution};
+
+    fn dataset() -> Dataset {
+        // p-1 has 3 users, p-2 has 2 and p-3 and p-4 have one each.
+        [
+            ("u-1", "p-1"),
+            ("u-1", "p-2"),
+            ("u-1", "p-3"),
+            ("u-2", "p-1"),
+            ("u-3", "p-1"),
+            ("u-3", "p-2"),
+            ("u-2", "p-4"),
+        ]
+        .iter()
+        .map(|(u, i)| Event::new(u.to_string(), i.to_string()))
+        .collect()
+    }
+
+    #[test]
+    fn should_never_sample_positives() {
+        let synthetic = SyntheticConfig::new(50, 30)
+            .with_density(0.5)
+            .with_seed(4)
+            .generate()
+            .unwrap();
+        let dataset = synthetic.dataset;
+
+        for distribution in [
+            NegativeDistribution::Uniform,
+            NegativeDistribution::Popularity { exponent: 0.75 },
+        ] {
+            let mut sampler = dataset.negative_sampler(distribution, 7).unwrap();
+            let samples = sampler.sample_interactions(2);
+            assert_eq!(dataset.cui.nnz() * 2, samples.len());
+            for s in samples {
+                assert!(dataset.cui.get(s.user_idx, s.positive_idx).is_some());
+                assert!(dataset.cui.get(s.user_idx, s.negative_idx).is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn should_return_distinct_negatives_and_run_out_of_them() {
+        let dataset = dataset();
+        let mut sampler = dataset
+            .negative_sampler(NegativeDistribution::Uniform, 1)
+            .unwrap();
+
+        // u-1 only misses p-4.
+        assert_eq!(vec![3], sampler.sample_for_user(0, 5));
+
+        let mut negatives = sampler.sample_for_user(1, 5);
+        negatives.sort();
+        assert_eq!(vec![1, 2], negatives);
+    }
+
+    #[test]
+    fn should_follow_popularity_and_be_seeded() {
+        let dataset = dataset();
+        let popularity = NegativeDistribution::Popularity { exponent: 1.0 };
+
+        let draws = (0..2000)
+            .map(|seed| {
+                dataset
+                    .negative_sampler(popularity, seed)
+                    .unwrap()
+                    .sample(1)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        // u-2 misses p-2 and p-3, p-2 being twice as popular.
+        let p2 = draws.iter().filter(|&&i| i == 1).count();
+        assert!((1200..1470).contains(&p2), "p-2 was drawn {} times", p2);
+
+        let first = dataset
+            .negative_sampler(popularity, 9)
+            .unwrap()
+            .sample_users(&[0, 1, 2], 2);
+        let second = dataset
+            .negative_sampler(popularity, 9)
+            .unwrap()
+            .sample_users(&[0, 1, 2], 2);
+        assert_eq!(first, second);
+
+        let negative = NegativeDistribution::Popularity { exponent: -1.0 };
+        assert!(matches!(
+            dataset.negative_sampler(negative, 9),
+            Err(DatasetError::InvalidConfig(_))
+        ));
+    }
+}
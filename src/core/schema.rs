@@ -0,0 +1,326 @@
+/// Points at a column of a delimited file, either by its header name or by its position.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CsvColumn {
+    Name(String),
+    Index(usize),
+}
+
+impl From<&str> for CsvColumn {
+    fn from(name: &str) -> Self {
+        CsvColumn::Name(name.to_string())
+    }
+}
+
+impl From<String> for CsvColumn {
+    fn from(name: String) -> Self {
+        CsvColumn::Name(name)
+    }
+}
+
+impl From<usize> for CsvColumn {
+    fn from(idx: usize) -> Self {
+        CsvColumn::Index(idx)
+    }
+}
+
+/// Describes where the fields of an [`Event`](super::model::Event) live in a csv file.
+///
+/// Only the user and item columns are required, everything else is opt-in:
+///
+/// ```
+/// use rs_mender::core::schema::CsvSchema;
+///
+/// let schema = CsvSchema::new("CustomerID", "StockCode")
+///     .with_timestamp("InvoiceDate")
+///     .with_delimiter(b';');
+/// ```
+#[derive(Clone, Debug)]
+pub struct CsvSchema {
+    pub user_id: CsvColumn,
+    pub item_id: CsvColumn,
+    pub timestamp: Option<CsvColumn>,
+    /// `chrono` format string of the timestamp column, see [`with_timestamp_format`](Self::with_timestamp_format).
+    pub timestamp_format: Option<String>,
+    pub weight: Option<CsvColumn>,
+    pub event_type: Option<CsvColumn>,
+    pub delimiter: u8,
+    pub has_headers: bool,
+}
+
+impl CsvSchema {
+    pub fn new(user_id: impl Into<CsvColumn>, item_id: impl Into<CsvColumn>) -> Self {
+        Self {
+            user_id: user_id.into(),
+            item_id: item_id.into(),
+            timestamp: None,
+            timestamp_format: None,
+            weight: None,
+            event_type: None,
+            delimiter: b',',
+            has_headers: true,
+        }
+    }
+
+    pub fn with_timestamp(mut self, column: impl Into<CsvColumn>) -> Self {
+        self.timestamp = Some(column.into());
+        self
+    }
+
+    /// Parses the timestamp column with a `chrono` format string such as `"%m/%d/%Y %H:%M"`.
+    ///
+    /// Without a format unix timestamps in seconds or milliseconds, RFC 3339 and
+    /// `%Y-%m-%d %H:%M:%S` are recognized. Timestamps without an offset are taken as UTC.
+    pub fn with_timestamp_format(mut self, format: impl Into<String>) -> Self {
+        self.timestamp_format = Some(format.into());
+        self
+    }
+
+    pub fn with_weight(mut self, column: impl Into<CsvColumn>) -> Self {
+        self.weight = Some(column.into());
+        self
+    }
+
+    pub fn with_event_type(mut self, column: impl Into<CsvColumn>) -> Self {
+        self.event_type = Some(column.into());
+        self
+    }
+
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// The first line is treated as data and columns can only be referenced by index.
+    pub fn without_headers(mut self) -> Self {
+        self.has_headers = false;
+        self
+    }
+}
+
+/// Layouts of public recommendation benchmarks, read as [`EventType::Rating`](super::model::EventType::Rating)
+/// events weighted by their rating.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BenchmarkFormat {
+    /// MovieLens 100K `u.data`, tab separated `user item rating timestamp` without a header.
+    MovieLens100K,
+    /// MovieLens 1M and 10M `ratings.dat`, `UserID::MovieID::Rating::Timestamp`.
+    MovieLensDat,
+    /// MovieLens 20M, 25M and latest `ratings.csv`, with a `userId,movieId,rating,timestamp` header.
+    MovieLensCsv,
+    /// Amazon reviews jsonl dumps, from 2014 up to 2023.
+    AmazonReviews,
+    /// Tab separated `user item rating [timestamp]` without a header.
+    RatingsTsv,
+}
+
+/// Describes where the fields of an [`Event`](super::model::Event) live in the objects of a jsonl
+/// file, as [JSON pointers](https://datatracker.ietf.org/doc/html/rfc6901) such as `/user/id`.
+///
+/// The item pointer can lead to a single id, giving one event per line, or to an array, giving
+/// one event per element. Elements are either ids themselves or objects holding the id at
+/// [`item_id_in_array`](Self::with_item_id_in_array). The weight, timestamp and event type of an
+/// object element are looked up in the element first, and in the whole line otherwise.
+///
+/// ```
+/// use rs_mender::core::schema::JsonlSchema;
+///
+/// // {"user": {"id": "u-1"}, "item": "p-1", "ts": 1690000000}
+/// let flat = JsonlSchema::new("/user/id", "/item").with_timestamp("/ts");
+///
+/// // {"customer": "u-1", "basket": [{"sku": "p-1", "qty": 2}, {"sku": "p-2", "qty": 1}]}
+/// let nested = JsonlSchema::new("/customer", "/basket")
+///     .with_item_id_in_array("/sku")
+///     .with_weight("/qty");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JsonlSchema {
+    pub user_id: String,
+    pub item_id: String,
+    pub item_id_in_array: Option<String>,
+    pub timestamp: Option<String>,
+    /// `chrono` format string of the timestamp, see [`CsvSchema::with_timestamp_format`].
+    pub timestamp_format: Option<String>,
+    pub weight: Option<String>,
+    pub event_type: Option<String>,
+}
+
+impl JsonlSchema {
+    /// Pointers without a leading `/` are taken as top level fields, `user_id` meaning `/user_id`.
+    pub fn new(user_id: impl Into<String>, item_id: impl Into<String>) -> Self {
+        Self {
+            user_id: pointer(user_id),
+            item_id: pointer(item_id),
+            item_id_in_array: None,
+            timestamp: None,
+            timestamp_format: None,
+            weight: None,
+            event_type: None,
+        }
+    }
+
+    /// The `{"user_id": .., "deliveries": [..]}` layout of the delivery logs.
+    pub fn deliveries() -> Self {
+        Self::new("/user_id", "/deliveries")
+    }
+
+    pub fn with_item_id_in_array(mut self, item_id: impl Into<String>) -> Self {
+        self.item_id_in_array = Some(pointer(item_id));
+        self
+    }
+
+    pub fn with_timestamp(mut self, timestamp: impl Into<String>) -> Self {
+        self.timestamp = Some(pointer(timestamp));
+        self
+    }
+
+    pub fn with_timestamp_format(mut self, format: impl Into<String>) -> Self {
+        self.timestamp_format = Some(format.into());
+        self
+    }
+
+    pub fn with_weight(mut self, weight: impl Into<String>) -> Self {
+        self.weight = Some(pointer(weight));
+        self
+    }
+
+    pub fn with_event_type(mut self, event_type: impl Into<String>) -> Self {
+        self.event_type = Some(pointer(event_type));
+        self
+    }
+}
+
+/// Describes the attributes of the entries of an item catalog or of user profiles, see
+/// [`FeatureStore`](super::catalog::FeatureStore).
+///
+/// Fields are header names in a csv file and [JSON pointers](https://datatracker.ietf.org/doc/html/rfc6901)
+/// in a jsonl file, see [`FeatureField`] for how the attributes are named. Categorical attributes
+/// can hold several values, given as a JSON array or as a csv field split on the
+/// [`list_separator`](Self::with_list_separator).
+///
+/// ```
+/// use rs_mender::core::schema::{FeatureField, FeatureSchema};
+///
+/// // item_id,category,brand,tags,price,description
+/// // p-1,shoes,acme,running|trail,89.90,Light trail running shoes
+/// let schema = FeatureSchema::new("item_id")
+///     .with_categorical("category")
+///     .with_categorical("brand")
+///     .with_categorical("tags")
+///     .with_numeric("price")
+///     .with_text("description");
+///
+/// // {"id": "p-1", "brand": {"name": "acme"}, "price": {"amount": 89.9}}
+/// let schema = FeatureSchema::new("/id")
+///     .with_categorical(FeatureField::new("/brand/name").named("brand"))
+///     .with_numeric("/price/amount");
+/// assert_eq!("price.amount", schema.numeric[0].name);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeatureSchema {
+    pub id: String,
+    pub categorical: Vec<FeatureField>,
+    pub numeric: Vec<FeatureField>,
+    pub text: Vec<FeatureField>,
+    pub list_separator: char,
+    pub delimiter: u8,
+}
+
+/// An attribute of a [`FeatureSchema`], read from the `source` column or JSON pointer and stored
+/// under `name`.
+///
+/// Unless [`named`](Self::named), the attribute is named after its source, the segments of a
+/// pointer being joined with dots, e.g. `/brand/name` becomes `brand.name`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeatureField {
+    pub name: String,
+    pub source: String,
+}
+
+impl FeatureField {
+    pub fn new(source: impl Into<String>) -> Self {
+        let source = source.into();
+        let name = match source.strip_prefix('/') {
+            Some(path) => path
+                .split('/')
+                .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+                .collect::<Vec<_>>()
+                .join("."),
+            None => source.clone(),
+        };
+        Self { name, source }
+    }
+
+    pub fn named(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+}
+
+impl From<&str> for FeatureField {
+    fn from(source: &str) -> Self {
+        Self::new(source)
+    }
+}
+
+impl From<String> for FeatureField {
+    fn from(source: String) -> Self {
+        Self::new(source)
+    }
+}
+
+impl FeatureSchema {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            categorical: Vec::new(),
+            numeric: Vec::new(),
+            text: Vec::new(),
+            list_separator: '|',
+            delimiter: b',',
+        }
+    }
+
+    pub fn with_categorical(mut self, attribute: impl Into<FeatureField>) -> Self {
+        self.categorical.push(attribute.into());
+        self
+    }
+
+    pub fn with_numeric(mut self, attribute: impl Into<FeatureField>) -> Self {
+        self.numeric.push(attribute.into());
+        self
+    }
+
+    pub fn with_text(mut self, attribute: impl Into<FeatureField>) -> Self {
+        self.text.push(attribute.into());
+        self
+    }
+
+    /// Separates the values of a categorical csv field, `|` by default.
+    pub fn with_list_separator(mut self, separator: char) -> Self {
+        self.list_separator = separator;
+        self
+    }
+
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+}
+
+pub(crate) fn pointer(path: impl Into<String>) -> String {
+    let path = path.into();
+    if path.is_empty() || path.starts_with('/') {
+        path
+    } else {
+        format!("/{}", path)
+    }
+}
+
+/// Layout of an input file, for the readers that work with any of them such as
+/// [`DatasetBuilder::read_shards`](super::dataset::DatasetBuilder::read_shards).
+#[derive(Clone, Debug)]
+pub enum InputFormat {
+    Jsonl(JsonlSchema),
+    Csv(CsvSchema),
+    Benchmark(BenchmarkFormat),
+}
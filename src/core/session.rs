@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+
+use super::{dataset::Dataset, error::DatasetError, model::Event};
+
+/// Consecutive events of a user, no two of them further apart than the inactivity gap they were
+/// grouped with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    id: String,
+    user_id: String,
+    events: Vec<Event>,
+}
+
+impl Session {
+    /// `{user_id}#{n}`, `n` counting the sessions of the user from 0 in time order.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn user_id(&self) -> &str {
+        &self.user_id
+    }
+
+    /// Events of the session, oldest first.
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// Items of the session in the order they were interacted with, repeats included.
+    pub fn items(&self) -> impl Iterator<Item = &str> + '_ {
+        self.events.iter().map(Event::target_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn start(&self) -> DateTime<Utc> {
+        self.events[0].timestamp().unwrap()
+    }
+
+    pub fn end(&self) -> DateTime<Utc> {
+        self.events[self.events.len() - 1].timestamp().unwrap()
+    }
+
+    /// The events of the session with the session id in place of the user id, so that sessions
+    /// become the rows of a dataset built from them.
+    pub fn into_events(self) -> impl Iterator<Item = Event> {
+        let id = self.id;
+        self.events.into_iter().map(move |event| {
+            Event::new(id.clone(), event.target_id().to_string())
+                .with_event_type(event.event_type().clone())
+                .with_weight(event.weight())
+                .with_timestamp(event.timestamp().unwrap())
+        })
+    }
+}
+
+/// Groups the events of each user into [`Session`]s, starting a new session whenever the user
+/// was inactive for longer than the gap.
+#[derive(Clone, Debug)]
+pub struct Sessionizer {
+    gap: Duration,
+    min_length: usize,
+}
+
+impl Sessionizer {
+    /// Fails with [`DatasetError::InvalidConfig`] when `gap` is not positive.
+    pub fn new(gap: Duration) -> Result<Self, DatasetError> {
+        if gap <= Duration::zero() {
+            return Err(DatasetError::InvalidConfig(format!(
+                "inactivity gap should be positive, got {}",
+                gap
+            )));
+        }
+        Ok(Self { gap, min_length: 1 })
+    }
+
+    /// Leaves out sessions with fewer events, e.g. `2` to drop single click sessions.
+    pub fn with_min_length(mut self, min_length: usize) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    /// Sessions of the events, grouped by user in order of their first event and by time within
+    /// a user. Events do not need to be sorted.
+    ///
+    /// Fails with [`DatasetError::MissingTimestamps`] on an event without a timestamp.
+    pub fn sessions<I: IntoIterator<Item = Event>>(
+        &self,
+        events: I,
+    ) -> Result<Vec<Session>, DatasetError> {
+        let mut users: Vec<Vec<Event>> = Vec::new();
+        let mut user_positions: HashMap<String, usize> = HashMap::new();
+        for event in events {
+            if event.timestamp().is_none() {
+                return Err(DatasetError::MissingTimestamps);
+            }
+            let position = *user_positions
+                .entry(event.user_id().to_string())
+                .or_insert_with(|| {
+                    users.push(Vec::new());
+                    users.len() - 1
+                });
+            users[position].push(event);
+        }
+
+        let mut sessions = Vec::new();
+        for mut events in users {
+            // the sort is stable, events of the same instant keep their order.
+            events.sort_by_key(|e| e.timestamp());
+            let user_id = events[0].user_id().to_string();
+
+            let mut user_sessions: Vec<Vec<Event>> = Vec::new();
+            let mut last_seen: Option<DateTime<Utc>> = None;
+            for event in events {
+                let timestamp = event.timestamp().unwrap();
+                match (user_sessions.last_mut(), last_seen) {
+                    (Some(session), Some(last)) if timestamp - last <= self.gap => {
+                        session.push(event)
+                    }
+                    _ => user_sessions.push(vec![event]),
+                }
+                last_seen = Some(timestamp);
+            }
+
+            sessions.extend(
+                user_sessions
+                    .into_iter()
+                    .filter(|events| events.len() >= self.min_length)
+                    .enumerate()
+                    .map(|(n, events)| Session {
+                        id: format!("{}#{}", user_id, n),
+                        user_id: user_id.clone(),
+                        events,
+                    }),
+            );
+        }
+
+        Ok(sessions)
+    }
+}
+
+impl Dataset {
+    /// Builds a session-item dataset, with a row per session named after its
+    /// [`id`](Session::id), so that engines can run on sessions instead of users.
+    pub fn from_sessions<I: IntoIterator<Item = Session>>(sessions: I) -> Dataset {
+        sessions
+            .into_iter()
+            .flat_map(Session::into_events)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod session_test {
+    use chrono::{Duration, TimeZone, Utc};
+
+    use crate::core::{dataset::Dataset, error::DatasetError, model::Event};
+
+    use super::Sessionizer;
+
+    fn event(user: &str, item: &str, minute: i64) -> Event {
+        Event::new(user.to_string(), item.to_string())
+            .with_timestamp(Utc.timestamp_opt(1_690_000_000 + minute * 60, 0).unwrap())
+    }
+
+    #[test]
+    fn should_split_sessions_on_inactivity() {
+        let events = vec![
+            event("u-1", "p-3", 70),
+            event("u-2", "p-1", 0),
+            event("u-1", "p-1", 0),
+            event("u-1", "p-2", 20),
+            event("u-1", "p-2", 29),
+            event("u-1", "p-4", 95),
+        ];
+
+        let sessions = Sessionizer::new(Duration::minutes(30))
+            .unwrap()
+            .sessions(events)
+            .unwrap();
+
+        let items = sessions
+            .iter()
+            .map(|s| (s.id(), s.items().collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("u-1#0", vec!["p-1", "p-2", "p-2"]),
+                ("u-1#1", vec!["p-3", "p-4"]),
+                ("u-2#0", vec!["p-1"]),
+            ],
+            items
+        );
+        assert_eq!(
+            Duration::minutes(29),
+            sessions[0].end() - sessions[0].start()
+        );
+
+        let dataset = Dataset::from_sessions(sessions);
+        assert_eq!((3, 4), dataset.cui.shape());
+        assert_eq!(Some(&2.0), dataset.cui.get(0, 1));
+        assert_eq!(Some("u-1#1"), dataset.user_idx().get_item(1));
+    }
+
+    #[test]
+    fn should_drop_short_sessions_and_reject_events_without_timestamps() {
+        let sessionizer = Sessionizer::new(Duration::minutes(30))
+            .unwrap()
+            .with_min_length(2);
+
+        let sessions = sessionizer
+            .sessions(vec![event("u-1", "p-1", 0), event("u-1", "p-2", 90)])
+            .unwrap();
+        assert!(sessions.is_empty());
+
+        let untimed = Event::new("u-1".to_string(), "p-1".to_string());
+        assert!(matches!(
+            sessionizer.sessions(vec![untimed]),
+            Err(DatasetError::MissingTimestamps)
+        ));
+
+        assert!(matches!(
+            Sessionizer::new(Duration::zero()),
+            Err(DatasetError::InvalidConfig(_))
+        ));
+    }
+}
@@ -1,19 +1,37 @@
-use super::model::{Event, RecommendationResponse};
+use super::{error::RecommenderError, model::RecommendationResponse};
+
+/// How training went, returned by [`SimilarityEngine::train`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrainingReport {
+    /// Training error after each epoch, e.g. the RMSE of the interactions.
+    pub epoch_errors: Vec<f64>,
+    /// Epoch whose model was kept, the last one that clearly lowered the error.
+    pub best_epoch: Option<usize>,
+    /// Whether training ended before its last epoch because the error stopped improving.
+    pub stopped_early: bool,
+    /// Mean percentile rank of the training interactions under the kept model.
+    pub train_mpr: Option<f64>,
+}
 
 pub trait SimilarityEngine {
-    fn train(&mut self);
+    /// Fails with [`RecommenderError::NumericalDivergence`] when training does not converge.
+    fn train(&mut self) -> Result<TrainingReport, RecommenderError>;
 
+    /// Fails with [`RecommenderError::UnknownUser`] for users the engine was not trained on, and
+    /// with [`RecommenderError::NotTrained`] before training.
     fn find_similar_by_user_id(
-        &mut self,
+        &self,
         user_id: String,
         n_items: usize,
-    ) -> Result<RecommendationResponse, ()>;
+    ) -> Result<RecommendationResponse, RecommenderError>;
 
+    /// Fails with [`RecommenderError::UnknownItem`] for items the engine was not trained on, and
+    /// with [`RecommenderError::NotTrained`] before training.
     fn find_similar_by_target_id(
         &self,
         target_id: String,
         n_items: usize,
-    ) -> Result<RecommendationResponse, ()>;
+    ) -> Result<RecommendationResponse, RecommenderError>;
 
-    fn save(self);
+    fn save(&self) -> Result<(), RecommenderError>;
 }
@@ -0,0 +1,197 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use sprs::CsMat;
+
+use super::{
+    dataset::{BuildParameters, Dataset},
+    error::DatasetError,
+    item_index::ItemIndex,
+};
+
+const MAGIC: &[u8; 8] = b"RSMDSET\0";
+
+/// Version of the snapshot layout written by [`Dataset::save`].
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+/// Leading part of a snapshot, readable without decoding the matrices.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotHeader {
+    pub version: u32,
+    /// (users, items)
+    pub shape: (usize, usize),
+    pub interactions: usize,
+    pub has_timestamps: bool,
+    /// CRC32 of the encoded matrices and indexes.
+    pub checksum: u32,
+    pub parameters: BuildParameters,
+}
+
+type SnapshotBody = (
+    CsMat<f64>,
+    CsMat<f64>,
+    Vec<String>,
+    Vec<String>,
+    Option<CsMat<i64>>,
+);
+
+// The file is laid out as the magic bytes, the little endian version, the length of the header
+// and then the bincode encoded header and body.
+impl Dataset {
+    /// Writes the matrices and indexes of the dataset to a versioned binary snapshot.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), DatasetError> {
+        let path = path.as_ref();
+
+        let body = bincode::serialize(&(
+            &self.cui,
+            self.ciu(),
+            self.user_idx.items().collect::<Vec<_>>(),
+            self.item_idx.items().collect::<Vec<_>>(),
+            self.last_seen_matrix(),
+        ))
+        .map_err(|e| DatasetError::corrupted(path, e.to_string()))?;
+
+        let header = SnapshotHeader {
+            version: SNAPSHOT_VERSION,
+            shape: self.cui.shape(),
+            interactions: self.cui.nnz(),
+            has_timestamps: self.has_timestamps(),
+            checksum: crc32fast::hash(&body),
+            parameters: self.parameters().clone(),
+        };
+        let header = bincode::serialize(&header)
+            .map_err(|e| DatasetError::corrupted(path, e.to_string()))?;
+
+        let file = File::create(path).map_err(|e| DatasetError::io(path, e))?;
+        let mut writer = BufWriter::new(file);
+        writer
+            .write_all(MAGIC)
+            .and_then(|_| writer.write_all(&SNAPSHOT_VERSION.to_le_bytes()))
+            .and_then(|_| writer.write_all(&(header.len() as u64).to_le_bytes()))
+            .and_then(|_| writer.write_all(&header))
+            .and_then(|_| writer.write_all(&body))
+            .and_then(|_| writer.flush())
+            .map_err(|e| DatasetError::io(path, e))
+    }
+
+    /// Reads a snapshot written by [`Dataset::save`], verifying its version and checksum.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Dataset, DatasetError> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|e| DatasetError::io(path, e))?;
+
+        let (header, body) = split_snapshot(path, &bytes)?;
+        if crc32fast::hash(body) != header.checksum {
+            return Err(DatasetError::corrupted(path, "checksum mismatch"));
+        }
+
+        let (cui, ciu, users, items, last_seen): SnapshotBody =
+            bincode::deserialize(body).map_err(|e| DatasetError::corrupted(path, e.to_string()))?;
+
+        let user_idx = users.into_iter().collect::<ItemIndex>();
+        let item_idx = items.into_iter().collect::<ItemIndex>();
+        if cui.shape() != header.shape || (user_idx.size(), item_idx.size()) != header.shape {
+            return Err(DatasetError::corrupted(
+                path,
+                "shape does not match the header",
+            ));
+        }
+
+        Ok(Dataset::new(cui, ciu, user_idx, item_idx)
+            .with_last_seen(last_seen)
+            .with_parameters(header.parameters))
+    }
+
+    /// Reads only the header of a snapshot, e.g. to check its shape or parameters.
+    pub fn snapshot_header<P: AsRef<Path>>(path: P) -> Result<SnapshotHeader, DatasetError> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|e| DatasetError::io(path, e))?;
+
+        split_snapshot(path, &bytes).map(|(header, _)| header)
+    }
+}
+
+fn split_snapshot<'a>(
+    path: &Path,
+    bytes: &'a [u8],
+) -> Result<(SnapshotHeader, &'a [u8]), DatasetError> {
+    let corrupted = |reason: &str| DatasetError::corrupted(path, reason);
+
+    if bytes.len() < 20 || &bytes[..8] != MAGIC {
+        return Err(corrupted("not a dataset snapshot"));
+    }
+    let version = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+    if version != SNAPSHOT_VERSION {
+        return Err(corrupted(&format!("unsupported version {}", version)));
+    }
+    let header_len = u64::from_le_bytes(bytes[12..20].try_into().unwrap()) as usize;
+    let header_end = 20usize
+        .checked_add(header_len)
+        .filter(|&end| end <= bytes.len())
+        .ok_or_else(|| corrupted("truncated header"))?;
+
+    let header: SnapshotHeader = bincode::deserialize(&bytes[20..header_end])
+        .map_err(|e| DatasetError::corrupted(path, e.to_string()))?;
+
+    Ok((header, &bytes[header_end..]))
+}
+
+#[cfg(test)]
+mod snapshot_test {
+    use std::{env, fs};
+
+    use chrono::{Duration, TimeZone, Utc};
+
+    use crate::core::{
+        dataset::{Aggregation, Dataset, DatasetBuilder, TimeDecay},
+        error::DatasetError,
+        model::Event,
+    };
+
+    use super::SNAPSHOT_VERSION;
+
+    #[test]
+    fn should_save_and_load_dataset() {
+        let start = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+        let mut builder = DatasetBuilder::new()
+            .with_aggregation(Aggregation::LogCount)
+            .with_time_decay(TimeDecay::new(Duration::days(30)).unwrap());
+        builder.extend((0..20).map(|i| {
+            Event::new(format!("u-{}", i % 3), format!("p-{}", i % 7))
+                .with_timestamp(start + Duration::days(i))
+        }));
+        let dataset = builder.build();
+
+        let path = env::temp_dir().join("rs_mender_snapshot.bin");
+        dataset.save(&path).unwrap();
+
+        let header = Dataset::snapshot_header(&path).unwrap();
+        assert_eq!(SNAPSHOT_VERSION, header.version);
+        assert_eq!((3, 7), header.shape);
+        assert_eq!(Aggregation::LogCount, header.parameters.aggregation);
+
+        let loaded = Dataset::load(&path).unwrap();
+        assert_eq!(dataset.cui, loaded.cui);
+        assert_eq!(dataset.ciu(), loaded.ciu());
+        assert!(dataset.user_idx.items().eq(loaded.user_idx.items()));
+        assert!(dataset.item_idx.items().eq(loaded.item_idx.items()));
+        assert_eq!(dataset.last_seen(1, 1), loaded.last_seen(1, 1));
+        assert_eq!(dataset.parameters(), loaded.parameters());
+
+        // flip a byte of the body
+        let mut bytes = fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        fs::write(&path, bytes).unwrap();
+
+        assert!(matches!(
+            Dataset::load(&path),
+            Err(DatasetError::Corrupted { .. })
+        ));
+
+        fs::remove_file(path).unwrap();
+    }
+}
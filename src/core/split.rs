@@ -0,0 +1,453 @@
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use ndarray_rand::rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use super::{
+    dataset::Dataset,
+    error::{DatasetError, RecommenderError},
+    similarity::SimilarityEngine,
+};
+
+/// How [`Dataset::split`] picks the interactions that are held out for testing.
+///
+/// Strategies working per user never hold out the last interaction of a user, so that every user
+/// of the test set is also known to the model trained on the train set.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SplitStrategy {
+    /// Holds out the given ratio of all interactions, picked at random.
+    Random { test_ratio: f64 },
+    /// Holds out one random interaction of each user.
+    LeaveOneOut,
+    /// Holds out the `n` most recent interactions of each user.
+    LeaveLastN(usize),
+    /// Holds out every interaction that happened at or after the cutoff.
+    TemporalCutoff(DateTime<Utc>),
+}
+
+impl Dataset {
+    /// Splits the interactions into a (train, test) pair.
+    ///
+    /// Both datasets keep the shape and the indexes of this one, so users and items can be looked
+    /// up the same way in either. Strategies that involve randomness are seeded with `seed`.
+    ///
+    /// Fails with [`DatasetError::InvalidConfig`] on a test ratio outside of `0..=1`.
+    pub fn split(
+        &self,
+        strategy: &SplitStrategy,
+        seed: u64,
+    ) -> Result<(Dataset, Dataset), DatasetError> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut held_out = vec![false; self.cui.nnz()];
+
+        match strategy {
+            SplitStrategy::Random { test_ratio } => {
+                if !(0.0..=1.0).contains(test_ratio) {
+                    return Err(DatasetError::InvalidConfig(format!(
+                        "test ratio should be between 0 and 1, got {}",
+                        test_ratio
+                    )));
+                }
+                let mut positions = (0..held_out.len()).collect_vec();
+                positions.shuffle(&mut rng);
+
+                let test_size = (*test_ratio * positions.len() as f64).round() as usize;
+                positions[..test_size]
+                    .iter()
+                    .for_each(|&p| held_out[p] = true);
+            }
+            SplitStrategy::LeaveOneOut => {
+                for (_, positions) in self.positions_by_user() {
+                    if positions.len() > 1 {
+                        let picked = positions.choose(&mut rng).unwrap();
+                        held_out[*picked] = true;
+                    }
+                }
+            }
+            SplitStrategy::LeaveLastN(n) => {
+                if !self.has_timestamps() {
+                    return Err(DatasetError::MissingTimestamps);
+                }
+                for (user, positions) in self.positions_by_user() {
+                    let by_recency = positions
+                        .iter()
+                        .copied()
+                        .sorted_by_key(|&p| {
+                            let item = self.cui.indices()[p];
+                            (self.last_seen_millis(user, item).unwrap_or(i64::MIN), item)
+                        })
+                        .rev()
+                        .take((*n).min(positions.len().saturating_sub(1)));
+                    by_recency.for_each(|p| held_out[p] = true);
+                }
+            }
+            SplitStrategy::TemporalCutoff(cutoff) => {
+                if !self.has_timestamps() {
+                    return Err(DatasetError::MissingTimestamps);
+                }
+                let cutoff = cutoff.timestamp_millis();
+                for (p, (_, (user, item))) in self.cui.iter().enumerate() {
+                    held_out[p] = self
+                        .last_seen_millis(user, item)
+                        .is_some_and(|t| t >= cutoff);
+                }
+            }
+        }
+
+        Ok(self.partition(&held_out))
+    }
+
+    /// Positions of each user's interactions in the order of `cui.iter()`.
+    fn positions_by_user(&self) -> impl Iterator<Item = (usize, Vec<usize>)> + '_ {
+        let indptr = self.cui.indptr();
+        (0..self.cui.rows()).map(move |user| {
+            let range = indptr.outer_inds_sz(user);
+            (user, range.collect_vec())
+        })
+    }
+}
+
+/// How [`KFold`] assigns interactions to folds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FoldStrategy {
+    /// Interactions are spread over the folds at random, regardless of their user.
+    #[default]
+    Interactions,
+    /// The interactions of each user are spread evenly over the folds, so that every user with at
+    /// least `k` interactions is present in every fold.
+    StratifiedByUser,
+}
+
+/// K-fold cross-validation over the interactions of a [`Dataset`].
+///
+/// Each fold is used once for validation while the other `k - 1` folds are used for training.
+/// Like [`Dataset::split`], the train and validation datasets keep the shape and the indexes of
+/// the original one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KFold {
+    k: usize,
+    strategy: FoldStrategy,
+    seed: u64,
+}
+
+impl KFold {
+    /// Fails with [`DatasetError::InvalidConfig`] when `k` is less than 2.
+    pub fn new(k: usize, seed: u64) -> Result<Self, DatasetError> {
+        if k < 2 {
+            return Err(DatasetError::InvalidConfig(format!(
+                "k-fold needs at least two folds, got {}",
+                k
+            )));
+        }
+
+        Ok(Self {
+            k,
+            strategy: FoldStrategy::default(),
+            seed,
+        })
+    }
+
+    pub fn with_strategy(mut self, strategy: FoldStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// Yields the (train, validation) pair of each fold. The pairs are built lazily.
+    pub fn split<'a>(&self, dataset: &'a Dataset) -> impl Iterator<Item = (Dataset, Dataset)> + 'a {
+        let folds = self.assign_folds(dataset);
+
+        (0..self.k).map(move |fold| {
+            let held_out = folds.iter().map(|&f| f == fold).collect_vec();
+            dataset.partition(&held_out)
+        })
+    }
+
+    /// Trains an engine on every fold and scores it on the validation part with `metric`.
+    ///
+    /// `make_engine` gets the training dataset of the fold, and `metric` the trained engine along
+    /// with the validation dataset. Stops at the first fold whose training or scoring fails.
+    pub fn evaluate<E, F, M>(
+        &self,
+        dataset: &Dataset,
+        mut make_engine: F,
+        mut metric: M,
+    ) -> Result<CrossValidationReport, RecommenderError>
+    where
+        E: SimilarityEngine,
+        F: FnMut(Dataset) -> E,
+        M: FnMut(&E, &Dataset) -> Result<f64, RecommenderError>,
+    {
+        let fold_scores = self
+            .split(dataset)
+            .map(|(train, validation)| {
+                let mut engine = make_engine(train);
+                engine.train()?;
+                metric(&engine, &validation)
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(CrossValidationReport { fold_scores })
+    }
+
+    /// Fold of each interaction, in the order of `cui.iter()`.
+    fn assign_folds(&self, dataset: &Dataset) -> Vec<usize> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut folds = vec![0; dataset.cui.nnz()];
+
+        match self.strategy {
+            FoldStrategy::Interactions => {
+                let mut positions = (0..folds.len()).collect_vec();
+                positions.shuffle(&mut rng);
+                for (i, p) in positions.into_iter().enumerate() {
+                    folds[p] = i % self.k;
+                }
+            }
+            FoldStrategy::StratifiedByUser => {
+                for (_, mut positions) in dataset.positions_by_user() {
+                    positions.shuffle(&mut rng);
+                    // start at a random fold, otherwise users with few interactions would all
+                    // end up in the first folds.
+                    let offset = rng.gen_range(0..self.k);
+                    for (i, p) in positions.into_iter().enumerate() {
+                        folds[p] = (i + offset) % self.k;
+                    }
+                }
+            }
+        }
+
+        folds
+    }
+}
+
+/// Scores of an engine over the folds of a [`KFold`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CrossValidationReport {
+    pub fold_scores: Vec<f64>,
+}
+
+impl CrossValidationReport {
+    pub fn mean(&self) -> f64 {
+        self.fold_scores.iter().sum::<f64>() / self.fold_scores.len() as f64
+    }
+
+    /// Sample standard deviation of the fold scores.
+    pub fn std_dev(&self) -> f64 {
+        let mean = self.mean();
+        let squared_diffs = self
+            .fold_scores
+            .iter()
+            .map(|s| (s - mean).powi(2))
+            .sum::<f64>();
+
+        (squared_diffs / (self.fold_scores.len() as f64 - 1.0)).sqrt()
+    }
+
+    pub fn min(&self) -> f64 {
+        self.fold_scores
+            .iter()
+            .copied()
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    pub fn max(&self) -> f64 {
+        self.fold_scores
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max)
+    }
+}
+
+#[cfg(test)]
+mod split_test {
+    use chrono::{Duration, TimeZone, Utc};
+
+    use crate::core::{
+        dataset::Dataset,
+        error::{DatasetError, RecommenderError},
+        model::{Event, RecommendationResponse},
+        similarity::{SimilarityEngine, TrainingReport},
+    };
+
+    use super::{FoldStrategy, KFold, SplitStrategy};
+
+    fn dataset() -> Dataset {
+        let start = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+        (0..40)
+            .map(|i| {
+                Event::new(format!("u-{}", i % 4), format!("p-{}", i))
+                    .with_timestamp(start + Duration::days(i))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn should_split_randomly_and_reproducibly() {
+        let dataset = dataset();
+
+        let strategy = SplitStrategy::Random { test_ratio: 0.25 };
+        let (train, test) = dataset.split(&strategy, 42).unwrap();
+        let (_, test_again) = dataset.split(&strategy, 42).unwrap();
+
+        assert_eq!(30, train.cui.nnz());
+        assert_eq!(10, test.cui.nnz());
+        assert_eq!(dataset.cui.shape(), train.cui.shape());
+        assert_eq!(dataset.cui.shape(), test.cui.shape());
+        assert_eq!(test.cui, test_again.cui);
+        assert_eq!(dataset.item_idx.get_item(7), test.item_idx.get_item(7));
+    }
+
+    #[test]
+    fn should_leave_one_out_per_user() {
+        let (train, test) = dataset().split(&SplitStrategy::LeaveOneOut, 7).unwrap();
+
+        assert_eq!(36, train.cui.nnz());
+        for user in 0..4 {
+            assert_eq!(1, test.cui.outer_view(user).unwrap().nnz());
+        }
+    }
+
+    #[test]
+    fn should_leave_the_latest_interactions_out() {
+        let dataset = dataset();
+        let (train, test) = dataset.split(&SplitStrategy::LeaveLastN(2), 0).unwrap();
+
+        assert_eq!(32, train.cui.nnz());
+        // u-0 interacted with every fourth product, the last ones being p-32 and p-36.
+        let held_out = test
+            .cui
+            .outer_view(0)
+            .unwrap()
+            .indices()
+            .iter()
+            .filter_map(|&i| test.item_idx.get_item(i))
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["p-32", "p-36"], held_out);
+        assert!(test
+            .last_seen(0, test.cui.outer_view(0).unwrap().indices()[0])
+            .is_some());
+    }
+
+    #[test]
+    fn should_split_at_a_cutoff() {
+        let cutoff = Utc.with_ymd_and_hms(2023, 1, 31, 0, 0, 0).unwrap();
+        let (train, test) = dataset()
+            .split(&SplitStrategy::TemporalCutoff(cutoff), 0)
+            .unwrap();
+
+        assert_eq!(30, train.cui.nnz());
+        assert_eq!(10, test.cui.nnz());
+    }
+
+    #[test]
+    fn should_need_timestamps_for_temporal_splits() {
+        let dataset: Dataset = (0..10)
+            .map(|i| Event::new(format!("u-{}", i % 2), format!("p-{}", i)))
+            .collect();
+
+        assert!(matches!(
+            dataset.split(&SplitStrategy::LeaveLastN(1), 0),
+            Err(DatasetError::MissingTimestamps)
+        ));
+    }
+
+    #[test]
+    fn should_reject_invalid_split_settings() {
+        let dataset: Dataset = (0..10)
+            .map(|i| Event::new(format!("u-{}", i % 2), format!("p-{}", i)))
+            .collect();
+
+        assert!(matches!(
+            dataset.split(&SplitStrategy::Random { test_ratio: 1.5 }, 0),
+            Err(DatasetError::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            KFold::new(1, 0),
+            Err(DatasetError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn should_use_every_interaction_once_for_validation() {
+        let dataset = dataset();
+
+        for strategy in [FoldStrategy::Interactions, FoldStrategy::StratifiedByUser] {
+            let k_fold = KFold::new(5, 3).unwrap().with_strategy(strategy);
+
+            let mut validated = 0;
+            for (train, validation) in k_fold.split(&dataset) {
+                assert_eq!(32, train.cui.nnz());
+                assert_eq!(dataset.cui.shape(), validation.cui.shape());
+                if strategy == FoldStrategy::StratifiedByUser {
+                    for user in 0..4 {
+                        assert_eq!(2, validation.cui.outer_view(user).unwrap().nnz());
+                    }
+                }
+                validated += validation.cui.nnz();
+            }
+            assert_eq!(dataset.cui.nnz(), validated);
+        }
+    }
+
+    /// Recommends the items with the most interactions.
+    struct PopularityEngine {
+        dataset: Dataset,
+        popularity: Vec<usize>,
+    }
+
+    impl SimilarityEngine for PopularityEngine {
+        fn train(&mut self) -> Result<TrainingReport, RecommenderError> {
+            self.popularity = (0..self.dataset.cui.cols())
+                .map(|i| self.dataset.ciu().outer_view(i).unwrap().nnz())
+                .collect();
+            Ok(TrainingReport::default())
+        }
+
+        fn find_similar_by_user_id(
+            &self,
+            user_id: String,
+            _n_items: usize,
+        ) -> Result<RecommendationResponse, RecommenderError> {
+            Err(RecommenderError::UnknownUser(user_id))
+        }
+
+        fn find_similar_by_target_id(
+            &self,
+            target_id: String,
+            _n_items: usize,
+        ) -> Result<RecommendationResponse, RecommenderError> {
+            Err(RecommenderError::UnknownItem(target_id))
+        }
+
+        fn save(&self) -> Result<(), RecommenderError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_evaluate_an_engine_on_every_fold() {
+        let dataset = dataset();
+
+        let report = KFold::new(4, 1)
+            .unwrap()
+            .evaluate(
+                &dataset,
+                |train| PopularityEngine {
+                    dataset: train,
+                    popularity: vec![],
+                },
+                |engine, validation| {
+                    // every product is interacted with once, so it is either in train or validation.
+                    let unseen = engine.popularity.iter().filter(|&&p| p == 0).count();
+                    Ok(unseen as f64 / validation.cui.nnz() as f64)
+                },
+            )
+            .unwrap();
+
+        assert_eq!(vec![1.0; 4], report.fold_scores);
+        assert_eq!(1.0, report.mean());
+        assert_eq!(0.0, report.std_dev());
+    }
+}
@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+
+use super::dataset::Dataset;
+
+/// Share of the most popular items counted as the head of the catalog by [`Dataset::stats`].
+pub const HEAD_ITEM_RATIO: f64 = 0.2;
+
+/// Summary of a distribution of counts.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Distribution {
+    pub min: usize,
+    pub median: usize,
+    pub p90: usize,
+    pub max: usize,
+    pub mean: f64,
+}
+
+impl Distribution {
+    fn of(mut counts: Vec<usize>) -> Self {
+        if counts.is_empty() {
+            return Self::default();
+        }
+        counts.sort_unstable();
+
+        // nearest-rank percentile
+        let percentile = |p: f64| {
+            let rank = (p * counts.len() as f64).ceil() as usize;
+            counts[rank.clamp(1, counts.len()) - 1]
+        };
+
+        Self {
+            min: counts[0],
+            median: percentile(0.5),
+            p90: percentile(0.9),
+            max: counts[counts.len() - 1],
+            mean: counts.iter().sum::<usize>() as f64 / counts.len() as f64,
+        }
+    }
+}
+
+/// Overview of the size, sparsity and popularity skew of a [`Dataset`].
+///
+/// Interactions are the distinct (user, item) pairs of the user-item matrix.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DatasetStats {
+    pub users: usize,
+    pub items: usize,
+    pub interactions: usize,
+    /// Share of the user-item matrix that is filled.
+    pub density: f64,
+    pub interactions_per_user: Distribution,
+    pub interactions_per_item: Distribution,
+    /// Number of items in the head, the [`HEAD_ITEM_RATIO`] most popular items.
+    pub head_items: usize,
+    /// Share of the interactions that went to the head items.
+    pub head_interaction_share: f64,
+    /// Number of items outside the head.
+    pub long_tail_items: usize,
+    /// Gini coefficient of item popularity, `0` when every item is equally popular and close to
+    /// `1` when a few items get all the interactions.
+    pub item_popularity_gini: f64,
+}
+
+impl Dataset {
+    pub fn stats(&self) -> DatasetStats {
+        let (users, items) = self.cui.shape();
+        let interactions = self.cui.nnz();
+
+        let per_user = (0..users)
+            .map(|u| self.cui.outer_view(u).map_or(0, |row| row.nnz()))
+            .collect::<Vec<_>>();
+        let mut per_item = (0..items)
+            .map(|i| self.ciu().outer_view(i).map_or(0, |row| row.nnz()))
+            .collect::<Vec<_>>();
+
+        let density = if users * items == 0 {
+            0.0
+        } else {
+            interactions as f64 / (users * items) as f64
+        };
+
+        per_item.sort_unstable_by(|a, b| b.cmp(a));
+        let head_items = (items as f64 * HEAD_ITEM_RATIO).ceil() as usize;
+        let head_interactions = per_item.iter().take(head_items).sum::<usize>();
+        let head_interaction_share = if interactions == 0 {
+            0.0
+        } else {
+            head_interactions as f64 / interactions as f64
+        };
+
+        DatasetStats {
+            users,
+            items,
+            interactions,
+            density,
+            interactions_per_user: Distribution::of(per_user),
+            interactions_per_item: Distribution::of(per_item.clone()),
+            head_items,
+            head_interaction_share,
+            long_tail_items: items - head_items,
+            item_popularity_gini: gini(per_item),
+        }
+    }
+}
+
+fn gini(mut values: Vec<usize>) -> f64 {
+    let total = values.iter().sum::<usize>() as f64;
+    if values.is_empty() || total == 0.0 {
+        return 0.0;
+    }
+    values.sort_unstable();
+
+    let n = values.len() as f64;
+    let weighted_sum = values
+        .iter()
+        .enumerate()
+        .map(|(i, &v)| (i + 1) as f64 * v as f64)
+        .sum::<f64>();
+
+    2.0 * weighted_sum / (n * total) - (n + 1.0) / n
+}
+
+#[cfg(test)]
+mod stats_test {
+    use crate::core::{dataset::Dataset, model::Event};
+
+    use super::{gini, Distribution};
+
+    #[test]
+    fn should_summarize_dataset() {
+        // p-0 is interacted with by every user, p-1..p-4 by a single one.
+        let dataset: Dataset = (0..5)
+            .flat_map(|u| {
+                vec![
+                    Event::new(format!("u-{}", u), "p-0".to_string()),
+                    Event::new(format!("u-{}", u), format!("p-{}", u)),
+                ]
+            })
+            .collect();
+
+        let stats = dataset.stats();
+
+        assert_eq!(5, stats.users);
+        assert_eq!(5, stats.items);
+        assert_eq!(9, stats.interactions);
+        assert_eq!(9.0 / 25.0, stats.density);
+        assert_eq!(
+            Distribution {
+                min: 1,
+                median: 2,
+                p90: 2,
+                max: 2,
+                mean: 1.8
+            },
+            stats.interactions_per_user
+        );
+        assert_eq!(5, stats.interactions_per_item.max);
+        assert_eq!(1, stats.interactions_per_item.median);
+        assert_eq!(1, stats.head_items);
+        assert_eq!(4, stats.long_tail_items);
+        assert_eq!(5.0 / 9.0, stats.head_interaction_share);
+        assert!(stats.item_popularity_gini > 0.3);
+
+        let json = serde_json::to_string(&stats).unwrap();
+        assert!(json.contains("\"item_popularity_gini\""));
+    }
+
+    #[test]
+    fn gini_should_work() {
+        assert_eq!(0.0, gini(vec![3, 3, 3, 3]));
+        assert_eq!(0.75, gini(vec![0, 0, 0, 8]));
+        assert_eq!(0.0, gini(vec![]));
+    }
+}
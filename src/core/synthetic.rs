@@ -0,0 +1,230 @@
+use std::collections::HashSet;
+
+use ndarray_rand::rand::{
+    distributions::{Distribution, WeightedIndex},
+    rngs::StdRng,
+    seq::SliceRandom,
+    Rng, SeedableRng,
+};
+use sprs::TriMat;
+
+use super::{dataset::Dataset, error::DatasetError, item_index::ItemIndex};
+
+/// Settings of a generated interaction dataset with a known latent structure.
+///
+/// Users and items are split into clusters. Each interaction of a user goes to an item of their
+/// own cluster with probability `cluster_affinity`, and to any item otherwise. Within those
+/// candidates items are picked with a power-law popularity, the `i`th most popular item being
+/// picked proportionally to `1 / i^popularity_exponent`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyntheticConfig {
+    pub users: usize,
+    pub items: usize,
+    pub clusters: usize,
+    /// Share of the user-item matrix to fill.
+    pub density: f64,
+    pub popularity_exponent: f64,
+    pub cluster_affinity: f64,
+    pub seed: u64,
+}
+
+impl SyntheticConfig {
+    pub fn new(users: usize, items: usize) -> Self {
+        Self {
+            users,
+            items,
+            clusters: 4,
+            density: 0.05,
+            popularity_exponent: 1.0,
+            cluster_affinity: 0.9,
+            seed: 0,
+        }
+    }
+
+    pub fn with_clusters(mut self, clusters: usize) -> Self {
+        self.clusters = clusters;
+        self
+    }
+
+    pub fn with_density(mut self, density: f64) -> Self {
+        self.density = density;
+        self
+    }
+
+    pub fn with_popularity_exponent(mut self, exponent: f64) -> Self {
+        self.popularity_exponent = exponent;
+        self
+    }
+
+    pub fn with_cluster_affinity(mut self, affinity: f64) -> Self {
+        self.cluster_affinity = affinity;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    fn validate(&self) -> Result<(), DatasetError> {
+        let reason = if self.users == 0 || self.items == 0 || self.clusters == 0 {
+            "users, items and clusters should not be empty"
+        } else if self.clusters > self.items {
+            "every cluster should have at least one item"
+        } else if !(self.density > 0.0 && self.density <= 1.0) {
+            "density should be in (0, 1]"
+        } else if !(0.0..=1.0).contains(&self.cluster_affinity) {
+            "cluster affinity should be in [0, 1]"
+        } else if !self.popularity_exponent.is_finite() {
+            "popularity exponent should be finite"
+        } else {
+            return Ok(());
+        };
+        Err(DatasetError::InvalidConfig(reason.to_string()))
+    }
+
+    /// Generates the dataset. The same config always generates the same dataset.
+    ///
+    /// Fails with [`DatasetError::InvalidConfig`] when a setting is out of its range.
+    pub fn generate(&self) -> Result<SyntheticDataset, DatasetError> {
+        self.validate()?;
+
+        let mut rng = StdRng::seed_from_u64(self.seed);
+
+        let user_clusters = (0..self.users)
+            .map(|u| u % self.clusters)
+            .collect::<Vec<_>>();
+        let mut item_clusters = (0..self.items)
+            .map(|i| i % self.clusters)
+            .collect::<Vec<_>>();
+        item_clusters.shuffle(&mut rng);
+
+        // popularity rank of each item, so that popular items are spread over the clusters.
+        let mut ranks = (1..=self.items).collect::<Vec<_>>();
+        ranks.shuffle(&mut rng);
+        let popularity = ranks
+            .iter()
+            .map(|&rank| (rank as f64).powf(-self.popularity_exponent))
+            .collect::<Vec<_>>();
+
+        // large exponents can round the weights of the least popular items down to zero.
+        let weights_error =
+            |e| DatasetError::InvalidConfig(format!("popularity exponent is too large, {}", e));
+        let any_item = WeightedIndex::new(&popularity).map_err(weights_error)?;
+        let cluster_items = (0..self.clusters)
+            .map(|c| {
+                let items = (0..self.items)
+                    .filter(|&i| item_clusters[i] == c)
+                    .collect::<Vec<_>>();
+                let weights = WeightedIndex::new(items.iter().map(|&i| popularity[i]))
+                    .map_err(weights_error)?;
+                Ok((items, weights))
+            })
+            .collect::<Result<Vec<_>, DatasetError>>()?;
+
+        let per_user = ((self.density * self.items as f64).round() as usize).clamp(1, self.items);
+
+        let mut cui = TriMat::new((self.users, self.items));
+        for (user, &cluster) in user_clusters.iter().enumerate() {
+            let mut picked = HashSet::new();
+            // popular items are picked again and again, so give up on filling the row at some point.
+            let mut attempts = 0;
+            while picked.len() < per_user && attempts < per_user * 50 {
+                attempts += 1;
+                let item = if rng.gen_bool(self.cluster_affinity) {
+                    let (items, weights) = &cluster_items[cluster];
+                    items[weights.sample(&mut rng)]
+                } else {
+                    any_item.sample(&mut rng)
+                };
+                if picked.insert(item) {
+                    cui.add_triplet(user, item, 1.0);
+                }
+            }
+        }
+
+        let user_idx = (0..self.users)
+            .map(|u| format!("user-{}", u))
+            .collect::<ItemIndex>();
+        let item_idx = (0..self.items)
+            .map(|i| format!("item-{}", i))
+            .collect::<ItemIndex>();
+
+        Ok(SyntheticDataset {
+            dataset: Dataset::from_cui(cui.to_csr(), user_idx, item_idx),
+            user_clusters,
+            item_clusters,
+        })
+    }
+}
+
+/// A generated [`Dataset`] along with the clusters it was planted with.
+///
+/// Users are named `user-{idx}` and items `item-{idx}`, their indexes in the dataset match `idx`.
+pub struct SyntheticDataset {
+    pub dataset: Dataset,
+    pub user_clusters: Vec<usize>,
+    pub item_clusters: Vec<usize>,
+}
+
+impl SyntheticDataset {
+    /// Share of the interactions that fall within the cluster of their user.
+    pub fn in_cluster_share(&self) -> f64 {
+        let in_cluster = self
+            .dataset
+            .cui
+            .iter()
+            .filter(|(_, (u, i))| self.user_clusters[*u] == self.item_clusters[*i])
+            .count();
+
+        in_cluster as f64 / self.dataset.cui.nnz() as f64
+    }
+}
+
+#[cfg(test)]
+mod synthetic_test {
+    use crate::core::error::DatasetError;
+
+    use super::SyntheticConfig;
+
+    #[test]
+    fn should_generate_reproducible_structured_data() {
+        let config = SyntheticConfig::new(100, 200)
+            .with_clusters(5)
+            .with_density(0.05)
+            .with_seed(11);
+
+        let synthetic = config.generate().unwrap();
+        let dataset = &synthetic.dataset;
+
+        assert_eq!((100, 200), dataset.cui.shape());
+        assert_eq!(100 * 10, dataset.cui.nnz());
+        assert_eq!(Some("item-42"), dataset.item_idx.get_item(42));
+        assert!(synthetic.in_cluster_share() > 0.8);
+
+        let stats = dataset.stats();
+        assert!(stats.item_popularity_gini > 0.3);
+
+        assert_eq!(dataset.cui, config.generate().unwrap().dataset.cui);
+        assert_ne!(
+            dataset.cui,
+            config.with_seed(12).generate().unwrap().dataset.cui
+        );
+    }
+
+    #[test]
+    fn should_reject_settings_out_of_range() {
+        for config in [
+            SyntheticConfig::new(0, 10),
+            SyntheticConfig::new(10, 3).with_clusters(4),
+            SyntheticConfig::new(10, 10).with_density(0.0),
+            SyntheticConfig::new(10, 10).with_cluster_affinity(1.5),
+            SyntheticConfig::new(10, 10).with_popularity_exponent(f64::NAN),
+        ] {
+            assert!(matches!(
+                config.generate(),
+                Err(DatasetError::InvalidConfig(_))
+            ));
+        }
+    }
+}
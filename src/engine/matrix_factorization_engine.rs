@@ -1,28 +1,53 @@
-use std::fs::File;
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
 
+use chrono::{DateTime, Utc};
 use itertools::Itertools;
-use ndarray::{Array, Array1, Array2};
-use ndarray_rand::{
-    rand,
-    rand_distr::{Distribution, Standard, Uniform},
-    RandomExt,
-};
-use sprs::TriMat;
+use ndarray::{Array, Array1, Array2, Axis};
+use ndarray_rand::{rand_distr::Uniform, RandomExt};
+use serde::Serialize;
+use sprs::CsMat;
 
 use crate::{
     core::{
         dataset::Dataset,
-        item_index::ItemIndex,
-        model::{Event, RecommendationResponse},
-        similarity::SimilarityEngine,
+        erasure::{DeletionRecord, Subject},
+        error::RecommenderError,
+        model::RecommendationResponse,
+        similarity::{SimilarityEngine, TrainingReport},
     },
     utils::approx_equal,
 };
 
+/// Errors of the ratings predicted for held-out interactions, see
+/// [`MatrixFactorizationEngine::evaluate_ratings`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RatingErrors {
+    pub rmse: f64,
+    pub mae: f64,
+    /// Number of ratings the errors were computed on.
+    pub count: usize,
+}
+
+/// Name of the engine in its [`RecommendationResponse`]s.
+pub const ENGINE_NAME: &str = "matrix_factorization";
+
+/// Factorizes the user-item matrix into user and item factors trained with SGD.
+///
+/// On [`FeedbackKind::Explicit`](crate::core::dataset::FeedbackKind::Explicit) datasets the
+/// model also learns the mean rating and a bias per user and per item, so that
+/// [`predict_rating`](Self::predict_rating) gives ratings on the scale of the dataset.
 pub struct MatrixFactorizationEngine {
     dataset: Dataset,
     u_matrix: Option<Array2<f64>>,
     v_matrix: Option<Array2<f64>>,
+    global_mean: f64,
+    user_bias: Option<Array1<f64>>,
+    item_bias: Option<Array1<f64>>,
+    trained_at: Option<DateTime<Utc>>,
+    model_dir: PathBuf,
 }
 
 impl MatrixFactorizationEngine {
@@ -31,67 +56,309 @@ impl MatrixFactorizationEngine {
             dataset,
             u_matrix: None,
             v_matrix: None,
+            global_mean: 0.0,
+            user_bias: None,
+            item_bias: None,
+            trained_at: None,
+            model_dir: PathBuf::from("./data"),
         }
     }
 
-    fn internal_predict(&self, user_idx: usize) -> Array1<(usize, f64)> {
-        // if the model is not trained yet we panic, we should handle this gracefully in the future.
-        match (&self.u_matrix, &self.v_matrix) {
-            (Some(u_matrix), Some(v_matrix)) => {
-                let item_recs = u_matrix
-                    .row(user_idx)
-                    .dot(&v_matrix.t())
-                    .indexed_iter()
-                    .sorted_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap())
-                    .map(|(a, &b)| (a, b))
-                    .collect::<Array1<_>>();
+    /// Directory [`save`](SimilarityEngine::save) writes the factor matrices, biases and mean
+    /// rating to, `./data` by default.
+    pub fn with_model_dir<P: AsRef<Path>>(mut self, model_dir: P) -> Self {
+        self.model_dir = model_dir.as_ref().to_path_buf();
+        self
+    }
+
+    /// Path of a factor matrix or bias written by [`save`](SimilarityEngine::save), e.g.
+    /// `u_matrix`, `user_bias` or `global_mean`.
+    pub fn factors_path(&self, name: &str) -> PathBuf {
+        factors_path(&self.model_dir, name)
+    }
+
+    /// Version of the trained model, the crate version and the time training ended, e.g.
+    /// `0.1.0+20240301T101500.123Z`.
+    pub fn model_version(&self) -> Option<String> {
+        self.trained_at.map(|t| {
+            format!(
+                "{}+{}",
+                env!("CARGO_PKG_VERSION"),
+                t.format("%Y%m%dT%H%M%S%.3fZ")
+            )
+        })
+    }
+
+    pub fn dataset(&self) -> &Dataset {
+        &self.dataset
+    }
 
-                item_recs
+    /// User factors, a row per user of the training dataset, once trained. Use
+    /// [`IndexMapping::reorder_rows`](crate::core::item_index::IndexMapping::reorder_rows) to
+    /// line them up with the users of another dataset.
+    pub fn u_matrix(&self) -> Option<&Array2<f64>> {
+        self.u_matrix.as_ref()
+    }
+
+    /// Item factors, a row per item of the training dataset, once trained.
+    pub fn v_matrix(&self) -> Option<&Array2<f64>> {
+        self.v_matrix.as_ref()
+    }
+
+    /// Predicted values of every item for a user, in item index order.
+    fn scores(&self, user_idx: usize) -> Result<Array1<f64>, RecommenderError> {
+        match (
+            &self.u_matrix,
+            &self.v_matrix,
+            &self.user_bias,
+            &self.item_bias,
+        ) {
+            (Some(u_matrix), Some(v_matrix), Some(user_bias), Some(item_bias)) => {
+                Ok(u_matrix.row(user_idx).dot(&v_matrix.t())
+                    + item_bias
+                    + (self.global_mean + user_bias[user_idx]))
             }
-            (_, _) => {
-                panic!("you should train the model before making a prediction")
+            (_, _, _, _) => Err(RecommenderError::NotTrained),
+        }
+    }
+
+    fn internal_predict(&self, user_idx: usize) -> Result<Array1<(usize, f64)>, RecommenderError> {
+        Ok(self
+            .scores(user_idx)?
+            .indexed_iter()
+            .sorted_by(|(_, a), (_, b)| b.total_cmp(a))
+            .map(|(a, &b)| (a, b))
+            .collect::<Array1<_>>())
+    }
+
+    /// Predicted rating of an item by a user, clamped to the rating scale of the dataset. On
+    /// implicit datasets this is the raw preference score.
+    ///
+    /// Fails when the user or the item is not in the training dataset, or before training.
+    pub fn predict_rating(&self, user_id: &str, item_id: &str) -> Result<f64, RecommenderError> {
+        let user_idx = self.user_idx(user_id)?;
+        let item_idx = self
+            .dataset
+            .item_idx
+            .lookup(item_id)
+            .ok_or_else(|| RecommenderError::UnknownItem(item_id.to_string()))?;
+
+        self.predict_idx(user_idx, item_idx)
+    }
+
+    fn item_id(&self, item_idx: usize) -> String {
+        self.dataset
+            .item_idx
+            .get_item(item_idx)
+            .unwrap()
+            .to_string()
+    }
+
+    fn user_idx(&self, user_id: &str) -> Result<usize, RecommenderError> {
+        self.dataset
+            .user_idx
+            .lookup(user_id)
+            .ok_or_else(|| RecommenderError::UnknownUser(user_id.to_string()))
+    }
+
+    fn predict_idx(&self, user_idx: usize, item_idx: usize) -> Result<f64, RecommenderError> {
+        let score = match (
+            &self.u_matrix,
+            &self.v_matrix,
+            &self.user_bias,
+            &self.item_bias,
+        ) {
+            (Some(u_matrix), Some(v_matrix), Some(user_bias), Some(item_bias)) => {
+                self.global_mean
+                    + user_bias[user_idx]
+                    + item_bias[item_idx]
+                    + u_matrix.row(user_idx).dot(&v_matrix.row(item_idx))
             }
+            (_, _, _, _) => return Err(RecommenderError::NotTrained),
+        };
+        Ok(self.dataset.feedback().clamp(score))
+    }
+
+    /// Deletes a user from the training dataset and their row from the trained factors and
+    /// biases, see [`Dataset::remove`]. The user factors and biases persisted by
+    /// [`save`](SimilarityEngine::save) are rewritten without the row, or deleted when the engine
+    /// has nothing trained to rewrite them with.
+    ///
+    /// Returns `None` when the user is not in the training dataset, and fails when the persisted
+    /// factors can not be rewritten, the user being already gone from memory.
+    pub fn remove_user(
+        &mut self,
+        user_id: &str,
+    ) -> Result<Option<DeletionRecord>, RecommenderError> {
+        self.remove(&Subject::User(user_id.to_string()))
+    }
+
+    /// Deletes an item from the training dataset and its row from the item factors, biases and
+    /// their persisted copy, see [`remove_user`](Self::remove_user).
+    pub fn remove_item(
+        &mut self,
+        item_id: &str,
+    ) -> Result<Option<DeletionRecord>, RecommenderError> {
+        self.remove(&Subject::Item(item_id.to_string()))
+    }
+
+    fn remove(&mut self, subject: &Subject) -> Result<Option<DeletionRecord>, RecommenderError> {
+        let Some(mut record) = self.dataset.remove(subject) else {
+            return Ok(None);
+        };
+        let idx = record.former_idx;
+
+        let (factors, bias, names) = match subject {
+            Subject::User(_) => (
+                &mut self.u_matrix,
+                &mut self.user_bias,
+                ["u_matrix", "user_bias"],
+            ),
+            Subject::Item(_) => (
+                &mut self.v_matrix,
+                &mut self.item_bias,
+                ["v_matrix", "item_bias"],
+            ),
+        };
+        if let Some(factors) = factors {
+            factors.remove_index(Axis(0), idx);
+            record.removed_from.push(names[0].to_string());
+        }
+        if let Some(bias) = bias {
+            bias.remove_index(Axis(0), idx);
+            record.removed_from.push(names[1].to_string());
         }
+
+        let factors_file = factors_path(&self.model_dir, names[0]);
+        if rewrite_persisted(&factors_file, factors.as_ref())? {
+            record.removed_from.push(factors_file.display().to_string());
+        }
+        let bias_file = factors_path(&self.model_dir, names[1]);
+        if rewrite_persisted(&bias_file, bias.as_ref())? {
+            record.removed_from.push(bias_file.display().to_string());
+        }
+
+        Ok(Some(record))
     }
 
-    pub fn calculate_mpr(&self) -> f64 {
-        // if the model is not trained yet we panic, we should handle this gracefully in the future.
-        match (&self.u_matrix, &self.v_matrix) {
-            (Some(_), Some(_)) => {
-                let mut total_mpr = 0f64;
-                for user_idx in 0..self.dataset.user_idx.size() {
-                    let actual = self.dataset.cui.outer_view(user_idx).unwrap();
-                    let recommendations = self.internal_predict(user_idx);
-
-                    let mut percentile_rank_summation = 0f64;
-                    for (actual_item_idx, _) in actual.iter() {
-                        let rank = recommendations
-                            .iter()
-                            .position(|(item_idx, _)| actual_item_idx == *item_idx)
-                            .unwrap();
-
-                        let percentile_rank = (rank + 1) as f64 / recommendations.len() as f64;
-                        percentile_rank_summation += percentile_rank;
-                    }
-                    let user_mpr = percentile_rank_summation / actual.iter().count() as f64;
-                    total_mpr += user_mpr;
-                }
+    /// RMSE and MAE of the predicted ratings of the interactions in `test`, a held-out split of
+    /// the training dataset as produced by [`Dataset::split`].
+    ///
+    /// Fails with [`RecommenderError::InvalidConfig`] when `test` does not share the indexes of
+    /// the training dataset, or has no interaction to predict.
+    pub fn evaluate_ratings(&self, test: &Dataset) -> Result<RatingErrors, RecommenderError> {
+        self.check_test_dataset(test)?;
+        if test.cui.nnz() == 0 {
+            return Err(RecommenderError::InvalidConfig(
+                "no rating to predict, the test dataset is empty".to_string(),
+            ));
+        }
 
-                let mpr = total_mpr / self.dataset.user_idx.size() as f64;
+        let mut squared_error = 0f64;
+        let mut absolute_error = 0f64;
+        for (rating, (user_idx, item_idx)) in test.cui.iter() {
+            let error = rating - self.predict_idx(user_idx, item_idx)?;
+            squared_error += error * error;
+            absolute_error += error.abs();
+        }
+
+        let count = test.cui.nnz();
+        Ok(RatingErrors {
+            rmse: (squared_error / count as f64).sqrt(),
+            mae: absolute_error / count as f64,
+            count,
+        })
+    }
 
-                println!("Mean Percentile Rank (MPR): {:.4}", mpr);
+    /// Mean percentile rank of the interactions the model was trained on.
+    pub fn calculate_mpr(&self) -> Result<f64, RecommenderError> {
+        self.mean_percentile_rank(&self.dataset.cui, None)
+    }
+
+    /// Mean percentile rank of the interactions in `test`, a held-out split of the training
+    /// dataset as produced by [`Dataset::split`]. Items a user already interacted with during
+    /// training are left out of their ranking, and so are interactions of `test` that were
+    /// already seen during training.
+    ///
+    /// Fails like [`evaluate_ratings`](Self::evaluate_ratings), and with
+    /// [`RecommenderError::InvalidConfig`] when `test` has no unseen interaction to rank.
+    pub fn evaluate_mpr(&self, test: &Dataset) -> Result<f64, RecommenderError> {
+        self.check_test_dataset(test)?;
+
+        self.mean_percentile_rank(&test.cui, Some(&self.dataset.cui))
+    }
 
-                mpr
+    fn check_test_dataset(&self, test: &Dataset) -> Result<(), RecommenderError> {
+        if self.dataset.cui.shape() != test.cui.shape() {
+            return Err(RecommenderError::InvalidConfig(format!(
+                "test dataset of shape {:?} should share the indexes of the training dataset of \
+                 shape {:?}",
+                test.cui.shape(),
+                self.dataset.cui.shape()
+            )));
+        }
+        Ok(())
+    }
+
+    fn mean_percentile_rank(
+        &self,
+        actual: &CsMat<f64>,
+        seen: Option<&CsMat<f64>>,
+    ) -> Result<f64, RecommenderError> {
+        if self.u_matrix.is_none() || self.v_matrix.is_none() {
+            return Err(RecommenderError::NotTrained);
+        }
+
+        let mut total_mpr = 0f64;
+        let mut evaluated_users = 0;
+        for user_idx in 0..actual.rows() {
+            let actual = actual.outer_view(user_idx).unwrap();
+            if actual.nnz() == 0 {
+                continue;
             }
-            (_, _) => {
-                panic!("you should train the model before calculating mpr")
+
+            let seen = seen.and_then(|seen| seen.outer_view(user_idx));
+            let recommendations = self
+                .internal_predict(user_idx)?
+                .into_iter()
+                .filter(|(item_idx, _)| seen.as_ref().is_none_or(|s| s.get(*item_idx).is_none()))
+                .collect_vec();
+
+            // items seen during training are not in the ranking, there is nothing to evaluate.
+            let ranks = actual
+                .iter()
+                .filter_map(|(actual_item_idx, _)| {
+                    recommendations
+                        .iter()
+                        .position(|(item_idx, _)| actual_item_idx == *item_idx)
+                })
+                .collect_vec();
+            if ranks.is_empty() {
+                continue;
             }
+
+            let percentile_rank_summation = ranks
+                .iter()
+                .map(|rank| (rank + 1) as f64 / recommendations.len() as f64)
+                .sum::<f64>();
+            total_mpr += percentile_rank_summation / ranks.len() as f64;
+            evaluated_users += 1;
+        }
+
+        if evaluated_users == 0 {
+            return Err(RecommenderError::InvalidConfig(
+                "no interaction to rank, the test dataset is empty or only holds interactions \
+                 seen during training"
+                    .to_string(),
+            ));
         }
+
+        Ok(total_mpr / evaluated_users as f64)
     }
 }
 
 impl SimilarityEngine for MatrixFactorizationEngine {
-    fn train(&mut self) {
+    fn train(&mut self) -> Result<TrainingReport, RecommenderError> {
         let latent_factors = 30;
 
         let learning_rate = 0.01;
@@ -99,6 +366,12 @@ impl SimilarityEngine for MatrixFactorizationEngine {
 
         let n_iter = 100;
 
+        if self.dataset.cui.nnz() == 0 {
+            return Err(RecommenderError::InvalidConfig(
+                "the training dataset has no interactions".to_string(),
+            ));
+        }
+
         let user_size = self.dataset.user_idx.size();
         let item_size = self.dataset.item_idx.size();
 
@@ -106,15 +379,36 @@ impl SimilarityEngine for MatrixFactorizationEngine {
         let mut v_matrix = Array::random((item_size, latent_factors), Uniform::new(-0.1, 0.1));
         let mut previous_validation_err = f64::MAX;
 
+        // implicit values are fitted by the factors alone, ratings around their mean and biases.
+        let explicit = self.dataset.feedback().is_explicit();
+        let global_mean = if explicit {
+            self.dataset.cui.data().iter().sum::<f64>() / self.dataset.cui.nnz() as f64
+        } else {
+            0.0
+        };
+        let mut user_bias = Array1::<f64>::zeros(user_size);
+        let mut item_bias = Array1::<f64>::zeros(item_size);
+
         let mut patience_count = 0;
+        // factors are only kept once training succeeded, a diverging run leaves the engine as is.
+        let mut best = None;
+        let mut report = TrainingReport::default();
 
         let non_zero_value_count = self.dataset.cui.iter().count();
 
-        for _ in 0..n_iter {
+        for epoch in 0..n_iter {
             let mut validation_err = 0.0;
             for (v, (i, j)) in self.dataset.cui.iter() {
-                let pred = u_matrix.row(i).dot(&v_matrix.row(j).t());
-                let error = f64::from(*v) - pred;
+                let pred = global_mean
+                    + user_bias[i]
+                    + item_bias[j]
+                    + u_matrix.row(i).dot(&v_matrix.row(j).t());
+                let error = *v - pred;
+
+                if explicit {
+                    user_bias[i] += learning_rate * (2.0 * error - 2.0 * lambda * user_bias[i]);
+                    item_bias[j] += learning_rate * (2.0 * error - 2.0 * lambda * item_bias[j]);
+                }
 
                 // Update U and V using SGD
                 let delta_u = -2.0 * error * &v_matrix.row(j) + 2.0 * lambda * &u_matrix.row(i);
@@ -131,89 +425,191 @@ impl SimilarityEngine for MatrixFactorizationEngine {
             validation_err /= non_zero_value_count as f64;
             validation_err = validation_err.sqrt();
 
-            println!("RMSE: {}", validation_err);
+            report.epoch_errors.push(validation_err);
+
+            // the factors can overflow to NaN before the error does.
+            let has_nan = |factors: &Array2<f64>| factors.iter().any(|v| v.is_nan());
+            if !validation_err.is_finite() || has_nan(&u_matrix) || has_nan(&v_matrix) {
+                return Err(RecommenderError::NumericalDivergence { epoch });
+            }
 
             if validation_err < previous_validation_err
                 && !approx_equal(validation_err, previous_validation_err, 1e-3)
             {
                 previous_validation_err = validation_err;
 
-                self.u_matrix = Some(u_matrix.clone());
-                self.v_matrix = Some(v_matrix.clone());
+                best = Some((
+                    u_matrix.clone(),
+                    v_matrix.clone(),
+                    user_bias.clone(),
+                    item_bias.clone(),
+                ));
+                report.best_epoch = Some(epoch);
 
                 patience_count = 0;
             } else {
                 patience_count += 1;
                 if patience_count >= 5 {
-                    println!("early breaking...");
+                    report.stopped_early = true;
                     break;
                 }
             }
         }
 
-        // should not have any NaN values
-        assert_eq!(
-            0,
-            self.u_matrix
-                .clone()
-                .unwrap()
-                .iter()
-                .filter(|v| v.is_nan())
-                .count()
-        );
-        assert_eq!(
-            0,
-            self.v_matrix
-                .clone()
-                .unwrap()
-                .iter()
-                .filter(|v| v.is_nan())
-                .count()
-        );
+        // the first epoch always improves on the initial error, so there is a best epoch.
+        let (u_matrix, v_matrix, user_bias, item_bias) = best.expect("no epoch was run");
+        self.u_matrix = Some(u_matrix);
+        self.v_matrix = Some(v_matrix);
+        self.user_bias = Some(user_bias);
+        self.item_bias = Some(item_bias);
+        self.global_mean = global_mean;
+        self.trained_at = Some(Utc::now());
 
-        self.calculate_mpr();
+        report.train_mpr = Some(self.calculate_mpr()?);
 
-        serde_json::to_writer_pretty(
-            File::create("./data/best_u_matrix.json").unwrap(),
-            &u_matrix,
-        )
-        .unwrap();
-        serde_json::to_writer_pretty(
-            File::create("./data/best_v_matrix.json").unwrap(),
-            &v_matrix,
-        )
-        .unwrap();
+        Ok(report)
     }
 
     fn find_similar_by_user_id(
-        &mut self,
+        &self,
         user_id: String,
         n_items: usize,
-    ) -> Result<RecommendationResponse, ()> {
-        let user_idx = self.dataset.user_idx.get_idx(user_id);
-
-        let binding = self.internal_predict(user_idx);
-        let predictions = binding
-            .iter()
-            .map(|(i, v)| {
-                let item = self.dataset.item_idx.get_item(*i);
-                (item, v)
-            })
+    ) -> Result<RecommendationResponse, RecommenderError> {
+        let user_idx = self.user_idx(&user_id)?;
+
+        let recommendations = self
+            .internal_predict(user_idx)?
+            .into_iter()
             .take(n_items)
-            .collect_vec();
-        println!("{:?}", predictions);
-        Err(())
+            .map(|(i, score)| (self.item_id(i), score));
+
+        Ok(RecommendationResponse::new(
+            ENGINE_NAME,
+            self.model_version().unwrap_or_default(),
+            recommendations,
+        ))
     }
 
+    /// Items whose factors are closest to the ones of the target by cosine similarity.
     fn find_similar_by_target_id(
         &self,
         target_id: String,
         n_items: usize,
-    ) -> Result<RecommendationResponse, ()> {
-        todo!()
+    ) -> Result<RecommendationResponse, RecommenderError> {
+        let target_idx = self
+            .dataset
+            .item_idx
+            .lookup(&target_id)
+            .ok_or_else(|| RecommenderError::UnknownItem(target_id.clone()))?;
+
+        let v_matrix = self.v_matrix.as_ref().ok_or(RecommenderError::NotTrained)?;
+
+        let norms = v_matrix.map_axis(Axis(1), |row| row.dot(&row).sqrt());
+        let similarities = v_matrix.dot(&v_matrix.row(target_idx)) / (&norms * norms[target_idx]);
+
+        let recommendations = similarities
+            .indexed_iter()
+            .filter(|(i, s)| *i != target_idx && s.is_finite())
+            .sorted_by(|(_, a), (_, b)| b.total_cmp(a))
+            .take(n_items)
+            .map(|(i, &similarity)| (self.item_id(i), similarity))
+            .collect_vec();
+
+        Ok(RecommendationResponse::new(
+            ENGINE_NAME,
+            self.model_version().unwrap_or_default(),
+            recommendations,
+        )
+        .with_reason(format!("similar to {}", target_id)))
+    }
+
+    /// Writes the best factor matrices and biases found during training, and the mean rating, to
+    /// the [model directory](MatrixFactorizationEngine::with_model_dir).
+    fn save(&self) -> Result<(), RecommenderError> {
+        let (Some(u_matrix), Some(v_matrix), Some(user_bias), Some(item_bias)) = (
+            &self.u_matrix,
+            &self.v_matrix,
+            &self.user_bias,
+            &self.item_bias,
+        ) else {
+            return Err(RecommenderError::NotTrained);
+        };
+
+        write_factors(&self.factors_path("u_matrix"), u_matrix)?;
+        write_factors(&self.factors_path("v_matrix"), v_matrix)?;
+        write_factors(&self.factors_path("user_bias"), user_bias)?;
+        write_factors(&self.factors_path("item_bias"), item_bias)?;
+        write_factors(&self.factors_path("global_mean"), &self.global_mean)
+    }
+}
+
+fn factors_path(model_dir: &Path, name: &str) -> PathBuf {
+    model_dir.join(format!("best_{}.json", name))
+}
+
+/// Writes factors as json, replacing the file only once they are fully written.
+fn write_factors<T: Serialize>(path: &Path, factors: &T) -> Result<(), RecommenderError> {
+    let mut staging = path.as_os_str().to_owned();
+    staging.push(".tmp");
+
+    let file = File::create(&staging).map_err(|e| RecommenderError::io(&staging, e))?;
+    serde_json::to_writer_pretty(file, factors)
+        .map_err(|e| RecommenderError::serialization(&staging, e))?;
+    fs::rename(&staging, path).map_err(|e| RecommenderError::io(path, e))
+}
+
+/// Rewrites a persisted file with the current factors, or deletes it when there are none.
+/// Returns whether there was a file to purge.
+fn rewrite_persisted<T: Serialize>(
+    path: &Path,
+    factors: Option<&T>,
+) -> Result<bool, RecommenderError> {
+    if !path.exists() {
+        return Ok(false);
+    }
+    match factors {
+        Some(factors) => write_factors(path, factors)?,
+        None => fs::remove_file(path).map_err(|e| RecommenderError::io(path, e))?,
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod matrix_factorization_engine_test {
+    use crate::core::{
+        dataset::{Aggregation, Dataset, DatasetBuilder, FeedbackKind},
+        error::RecommenderError,
+        model::Event,
+        similarity::SimilarityEngine,
+    };
+
+    use super::MatrixFactorizationEngine;
+
+    fn ratings(rating: impl Fn(usize) -> f64, max: f64) -> Dataset {
+        let mut builder = DatasetBuilder::new()
+            .with_aggregation(Aggregation::Latest)
+            .with_feedback(FeedbackKind::rating_scale(0.0, max).unwrap());
+        builder.extend((0..20).map(|i| {
+            Event::new(format!("user-{}", i % 4), format!("item-{}", i)).with_weight(rating(i))
+        }));
+        builder.build()
     }
 
-    fn save(self) {
-        todo!()
+    #[test]
+    fn should_keep_the_previous_model_when_retraining_diverges() {
+        let mut engine = MatrixFactorizationEngine::new(ratings(|i| (i % 5 + 1) as f64, 5.0));
+        engine.train().unwrap();
+        let global_mean = engine.global_mean;
+        let u_matrix = engine.u_matrix.clone();
+
+        // ratings far apart from their mean overflow the squared errors right away.
+        engine.dataset = ratings(|i| if i % 2 == 0 { 1e200 } else { 0.0 }, 1e200);
+        assert!(matches!(
+            engine.train(),
+            Err(RecommenderError::NumericalDivergence { epoch: 0 })
+        ));
+
+        assert_eq!(global_mean, engine.global_mean);
+        assert_eq!(u_matrix, engine.u_matrix);
     }
 }
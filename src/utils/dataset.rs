@@ -1,65 +1,646 @@
 use std::{
-    fs::File,
-    io::{BufRead, BufReader},
-    path::Path,
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+    sync::mpsc::channel,
 };
 
+use chrono::{TimeZone, Utc};
+use csv::StringRecord;
+use flate2::bufread::MultiGzDecoder;
 use serde::Deserialize;
+use serde_json::Value;
+use threadpool::ThreadPool;
 
-use crate::core::model::Event;
+use crate::{
+    core::{
+        catalog::{FeatureLoadReport, Features},
+        dataset::{LoadReport, ParseMode},
+        error::DatasetError,
+        model::{Event, EventType},
+        schema::{
+            pointer, BenchmarkFormat, CsvColumn, CsvSchema, FeatureField, FeatureSchema,
+            InputFormat, JsonlSchema,
+        },
+    },
+    utils::parse_timestamp,
+};
 
-#[derive(Debug, Deserialize)]
-pub struct TestEvent {
-    user_id: String,
-    deliveries: Vec<String>,
+/// Opens a file for reading, decompressing it on the fly when it starts with the magic bytes of
+/// gzip or zstd.
+pub fn open(path: &Path) -> Result<Box<dyn BufRead + Send>, DatasetError> {
+    let file = File::open(path).map_err(|e| DatasetError::io(path, e))?;
+    let mut reader = BufReader::new(file);
+
+    let magic = reader.fill_buf().map_err(|e| DatasetError::io(path, e))?;
+    if magic.starts_with(&[0x1f, 0x8b]) {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        let decoder = zstd::Decoder::with_buffer(reader).map_err(|e| DatasetError::io(path, e))?;
+        Ok(Box::new(BufReader::new(decoder)))
+    } else {
+        Ok(Box::new(reader))
+    }
 }
 
-impl TestEvent {
-    pub fn to_events(&self) -> Vec<Event> {
-        self.deliveries
-            .iter()
-            .map(|d| Event::new(self.user_id.clone(), d.clone()))
-            .collect()
+/// Reads the events of a file in any of the supported formats.
+pub fn read_events<F: FnMut(Event)>(
+    path: &Path,
+    format: &InputFormat,
+    mode: ParseMode,
+    mut on_event: F,
+) -> Result<LoadReport, DatasetError> {
+    match format {
+        InputFormat::Jsonl(schema) => read_jsonl(path, schema, mode, on_event),
+        InputFormat::Csv(schema) => read_csv(path, schema, mode, on_event),
+        InputFormat::Benchmark(BenchmarkFormat::MovieLens100K | BenchmarkFormat::RatingsTsv) => {
+            read_separated_ratings(path, "\t", mode, on_event)
+        }
+        InputFormat::Benchmark(BenchmarkFormat::MovieLensDat) => {
+            read_separated_ratings(path, "::", mode, on_event)
+        }
+        InputFormat::Benchmark(BenchmarkFormat::MovieLensCsv) => {
+            let schema = CsvSchema::new("userId", "movieId")
+                .with_weight("rating")
+                .with_timestamp("timestamp");
+            read_csv(path, &schema, mode, |e| {
+                on_event(e.with_event_type(EventType::Rating))
+            })
+        }
+        InputFormat::Benchmark(BenchmarkFormat::AmazonReviews) => {
+            read_amazon_reviews(path, mode, on_event)
+        }
     }
 }
 
-pub fn read_test_data<F: FnMut(TestEvent)>(path: &Path, filter_lt: usize, mut on_event: F) {
-    let mut reader = BufReader::new(File::open(path).unwrap());
+/// Finds the shards of a sharded input, sorted by path.
+///
+/// `pattern` is either a directory, whose files are the shards, a glob pattern such as
+/// `exports/part-*.jsonl.gz`, or a single file. Hidden files and files starting with `_`, such as
+/// `_SUCCESS` markers, are not considered shards of a directory.
+pub fn shard_paths(pattern: &Path) -> Result<Vec<PathBuf>, DatasetError> {
+    let mut paths = if pattern.is_dir() {
+        fs::read_dir(pattern)
+            .map_err(|e| DatasetError::io(pattern, e))?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| DatasetError::io(pattern, e))?
+            .into_iter()
+            .filter(|p| p.is_file())
+            .filter(|p| {
+                p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| !n.starts_with('.') && !n.starts_with('_'))
+            })
+            .collect::<Vec<_>>()
+    } else if pattern.to_string_lossy().contains(['*', '?', '[']) {
+        let pattern_str = pattern.to_string_lossy();
+        glob::glob(&pattern_str)
+            .map_err(|e| DatasetError::schema(pattern, e.to_string()))?
+            .filter_map(Result::ok)
+            .filter(|p| p.is_file())
+            .collect::<Vec<_>>()
+    } else {
+        vec![pattern.to_path_buf()]
+    };
+
+    if paths.is_empty() {
+        let e = io::Error::new(io::ErrorKind::NotFound, "no shards found");
+        return Err(DatasetError::io(pattern, e));
+    }
+    paths.sort();
+
+    Ok(paths)
+}
+
+/// Parses the shards on `threads` threads, and passes their events to `on_event` shard by shard
+/// in the order of `paths`, so that the result does not depend on which shard finishes first.
+pub fn read_shards<F: FnMut(Event)>(
+    paths: &[PathBuf],
+    format: &InputFormat,
+    mode: ParseMode,
+    threads: usize,
+    mut on_event: F,
+) -> Result<LoadReport, DatasetError> {
+    let pool = ThreadPool::new(threads.max(1));
+    let (tx, rx) = channel();
+
+    for (shard, path) in paths.iter().enumerate() {
+        let tx = tx.clone();
+        let path = path.clone();
+        let format = format.clone();
+        pool.execute(move || {
+            let mut events = Vec::new();
+            let result = read_events(&path, &format, mode, |e| events.push(e));
+            // the receiver is gone when another shard failed already.
+            let _ = tx.send((shard, result.map(|report| (events, report))));
+        });
+    }
+    drop(tx);
+
+    let mut report = LoadReport::default();
+    let mut finished = HashMap::new();
+    let mut next = 0;
+    for (shard, result) in rx.iter() {
+        finished.insert(shard, result);
+        while let Some(result) = finished.remove(&next) {
+            let (events, shard_report) = result?;
+            events.into_iter().for_each(&mut on_event);
+            report.merge(shard_report);
+            next += 1;
+        }
+    }
+
+    if next < paths.len() {
+        let e = io::Error::other("reading the shard panicked");
+        return Err(DatasetError::io(&paths[next], e));
+    }
+
+    Ok(report)
+}
+
+/// Reads the events of a jsonl file laid out as described by `schema`.
+///
+/// A line yields all of its events or, when any of them is invalid, none of them.
+pub fn read_jsonl<F: FnMut(Event)>(
+    path: &Path,
+    schema: &JsonlSchema,
+    mode: ParseMode,
+    mut on_event: F,
+) -> Result<LoadReport, DatasetError> {
+    read_lines(path, mode, |line| {
+        let value: Value = serde_json::from_str(line).map_err(|e| e.to_string())?;
+        let events = jsonl_events(&value, schema)?;
+
+        let count = events.len();
+        events.into_iter().for_each(&mut on_event);
+        Ok(count)
+    })
+}
 
+fn jsonl_events(line: &Value, schema: &JsonlSchema) -> Result<Vec<Event>, String> {
+    let user_id = line
+        .pointer(&schema.user_id)
+        .and_then(json_id)
+        .ok_or_else(|| format!("missing user id at `{}`", schema.user_id))?;
+
+    let items = match line.pointer(&schema.item_id) {
+        Some(Value::Array(elements)) => elements.iter().collect::<Vec<_>>(),
+        Some(element) => vec![element],
+        None => return Err(format!("missing item id at `{}`", schema.item_id)),
+    };
+
+    items
+        .into_iter()
+        .map(|element| {
+            let item_id = match &schema.item_id_in_array {
+                Some(item_pointer) => element.pointer(item_pointer),
+                None => Some(element),
+            }
+            .and_then(json_id)
+            .ok_or_else(|| format!("invalid item id at `{}`", schema.item_id))?;
+
+            // fields of the element win over the ones of the whole line.
+            let field = |pointer: &str| element.pointer(pointer).or_else(|| line.pointer(pointer));
+
+            let mut event = Event::new(user_id.clone(), item_id);
+            if let Some(pointer) = &schema.weight {
+                let weight = field(pointer)
+                    .and_then(|w| w.as_f64().or_else(|| w.as_str()?.trim().parse().ok()))
+                    .filter(|w| w.is_finite())
+                    .ok_or_else(|| format!("missing or invalid weight at `{}`", pointer))?;
+                event = event.with_weight(weight);
+            }
+            if let Some(pointer) = &schema.timestamp {
+                let timestamp = field(pointer)
+                    .and_then(json_id)
+                    .and_then(|t| parse_timestamp(&t, schema.timestamp_format.as_deref()))
+                    .ok_or_else(|| format!("missing or invalid timestamp at `{}`", pointer))?;
+                event = event.with_timestamp(timestamp);
+            }
+            if let Some(pointer) = &schema.event_type {
+                let event_type = field(pointer)
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| format!("missing event type at `{}`", pointer))?;
+                event = event.with_event_type(event_type.parse().unwrap());
+            }
+
+            Ok(event)
+        })
+        .collect()
+}
+
+/// Ids can be written as strings or as numbers.
+fn json_id(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// Calls `on_line` with every non-empty line of the file.
+///
+/// `on_line` returns the number of events it got out of the line, or why the line is invalid.
+fn read_lines<F>(path: &Path, mode: ParseMode, mut on_line: F) -> Result<LoadReport, DatasetError>
+where
+    F: FnMut(&str) -> Result<usize, String>,
+{
+    let mut reader = open(path)?;
+
+    let mut report = LoadReport::default();
     let mut buffer = String::new();
-    while let Ok(bytes_read) = reader.read_line(&mut buffer) {
+    let mut line = 0;
+    loop {
+        buffer.clear();
+        let bytes_read = reader
+            .read_line(&mut buffer)
+            .map_err(|e| DatasetError::io(path, e))?;
         if bytes_read == 0 {
             break;
         }
-        let s: TestEvent = serde_json::from_str(&buffer).unwrap();
+        line += 1;
+        if buffer.trim().is_empty() {
+            continue;
+        }
+        report.records_read += 1;
 
-        if s.deliveries.len() >= filter_lt {
-            on_event(s);
+        match on_line(buffer.trim_end_matches(['\r', '\n'])) {
+            Ok(events) => report.events_loaded += events,
+            Err(reason) => report.reject(mode, path, line, reason)?,
         }
-        buffer.clear();
     }
+
+    Ok(report)
+}
+
+/// Reads one [`EventType::Rating`] event per line out of fields separated by `separator`, in the
+/// `user item rating [timestamp]` order used by MovieLens `ratings.dat` files.
+pub fn read_separated_ratings<F: FnMut(Event)>(
+    path: &Path,
+    separator: &str,
+    mode: ParseMode,
+    mut on_event: F,
+) -> Result<LoadReport, DatasetError> {
+    read_lines(path, mode, |line| {
+        let fields = line.split(separator).map(str::trim).collect::<Vec<_>>();
+        if fields.len() < 3 {
+            return Err(format!("expected at least 3 fields, got {}", fields.len()));
+        }
+
+        let mut event = rating_event(fields[0], fields[1], fields[2])?;
+        if let Some(timestamp) = fields.get(3) {
+            let t = parse_timestamp(timestamp, None)
+                .ok_or_else(|| format!("invalid timestamp `{}`", timestamp))?;
+            event = event.with_timestamp(t);
+        }
+
+        on_event(event);
+        Ok(1)
+    })
 }
 
 #[derive(Debug, Deserialize)]
-pub struct ECommerceRecord {
-    #[serde(alias = "CustomerID", deserialize_with = "csv::invalid_option")]
-    user_id: Option<String>,
-    #[serde(alias = "Description", deserialize_with = "csv::invalid_option")]
-    product_id: Option<String>,
+struct AmazonReview {
+    #[serde(alias = "reviewerID")]
+    user_id: String,
+    asin: String,
+    #[serde(alias = "overall")]
+    rating: f64,
+    // seconds in the 2014 and 2018 dumps, milliseconds as `timestamp` in the 2023 one.
+    #[serde(rename = "unixReviewTime")]
+    unix_review_time: Option<i64>,
+    timestamp: Option<i64>,
 }
 
-pub fn read_ecommerce_data<F: FnMut(Event)>(path: &Path, mut on_event: F) {
-    let mut reader = csv::Reader::from_path(path).unwrap();
+/// Reads the reviews of an Amazon reviews jsonl dump as [`EventType::Rating`] events.
+///
+/// Both the `reviewerID`/`overall`/`unixReviewTime` fields of the 2014 and 2018 dumps and the
+/// `user_id`/`rating`/`timestamp` fields of the 2023 dump are understood.
+pub fn read_amazon_reviews<F: FnMut(Event)>(
+    path: &Path,
+    mode: ParseMode,
+    mut on_event: F,
+) -> Result<LoadReport, DatasetError> {
+    read_lines(path, mode, |line| {
+        let review: AmazonReview = serde_json::from_str(line).map_err(|e| e.to_string())?;
 
-    for record in reader.deserialize() {
-        if record.is_err() {
-            continue;
+        let timestamp = match (review.unix_review_time, review.timestamp) {
+            (Some(seconds), _) => Utc.timestamp_opt(seconds, 0).single(),
+            (None, Some(millis)) => Utc.timestamp_millis_opt(millis).single(),
+            (None, None) => None,
+        };
+
+        let mut event = Event::new(review.user_id, review.asin)
+            .with_event_type(EventType::Rating)
+            .with_weight(review.rating);
+        if let Some(timestamp) = timestamp {
+            event = event.with_timestamp(timestamp);
         }
-        let e: ECommerceRecord = record.unwrap();
 
-        if e.user_id.is_none() || e.product_id.is_none() {
-            continue;
+        on_event(event);
+        Ok(1)
+    })
+}
+
+fn rating_event(user_id: &str, item_id: &str, rating: &str) -> Result<Event, String> {
+    if user_id.is_empty() || item_id.is_empty() {
+        return Err("missing user or item id".to_string());
+    }
+    let rating = rating
+        .parse::<f64>()
+        .map_err(|e| format!("invalid rating `{}`: {}", rating, e))?;
+
+    Ok(Event::new(user_id.to_string(), item_id.to_string())
+        .with_event_type(EventType::Rating)
+        .with_weight(rating))
+}
+
+struct CsvColumns {
+    user_id: usize,
+    item_id: usize,
+    timestamp: Option<usize>,
+    weight: Option<usize>,
+    event_type: Option<usize>,
+}
+
+impl CsvColumns {
+    fn resolve(
+        path: &Path,
+        schema: &CsvSchema,
+        headers: Option<&StringRecord>,
+    ) -> Result<Self, DatasetError> {
+        let position = |column: &CsvColumn| match (column, headers) {
+            (CsvColumn::Index(idx), _) => Ok(*idx),
+            (CsvColumn::Name(name), Some(headers)) => headers
+                .iter()
+                .position(|h| h.trim() == name)
+                .ok_or_else(|| {
+                    DatasetError::schema(path, format!("column `{}` is not in the header", name))
+                }),
+            (CsvColumn::Name(name), None) => Err(DatasetError::schema(
+                path,
+                format!(
+                    "column `{}` can not be found by name without a header",
+                    name
+                ),
+            )),
+        };
+
+        Ok(Self {
+            user_id: position(&schema.user_id)?,
+            item_id: position(&schema.item_id)?,
+            timestamp: schema.timestamp.as_ref().map(position).transpose()?,
+            weight: schema.weight.as_ref().map(position).transpose()?,
+            event_type: schema.event_type.as_ref().map(position).transpose()?,
+        })
+    }
+
+    fn to_event(
+        &self,
+        record: &StringRecord,
+        timestamp_format: Option<&str>,
+    ) -> Result<Event, String> {
+        let field = |idx: usize, name: &str| match record.get(idx).map(str::trim) {
+            Some(value) if !value.is_empty() => Ok(value),
+            _ => Err(format!("missing {}", name)),
+        };
+
+        let user_id = field(self.user_id, "user id")?;
+        let item_id = field(self.item_id, "item id")?;
+
+        let mut event = Event::new(user_id.to_string(), item_id.to_string());
+
+        if let Some(idx) = self.weight {
+            let weight = field(idx, "weight")?;
+            match weight.parse::<f64>() {
+                Ok(w) if w.is_finite() => event = event.with_weight(w),
+                Ok(_) => return Err(format!("invalid weight `{}`", weight)),
+                Err(e) => return Err(format!("invalid weight `{}`: {}", weight, e)),
+            }
+        }
+        if let Some(idx) = self.timestamp {
+            let timestamp = field(idx, "timestamp")?;
+            match parse_timestamp(timestamp, timestamp_format) {
+                Some(t) => event = event.with_timestamp(t),
+                None => return Err(format!("invalid timestamp `{}`", timestamp)),
+            }
+        }
+        if let Some(idx) = self.event_type {
+            let event_type = field(idx, "event type")?;
+            event = event.with_event_type(event_type.parse().unwrap());
+        }
+
+        Ok(event)
+    }
+}
+
+/// Reads the events of a delimited file laid out as described by `schema`.
+///
+/// Rows that can not be turned into an [`Event`] fail the read in [`ParseMode::Strict`], in
+/// [`ParseMode::Lenient`] they are recorded in the returned [`LoadReport`] instead.
+pub fn read_csv<F: FnMut(Event)>(
+    path: &Path,
+    schema: &CsvSchema,
+    mode: ParseMode,
+    mut on_event: F,
+) -> Result<LoadReport, DatasetError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(schema.delimiter)
+        .has_headers(schema.has_headers)
+        .flexible(true)
+        .from_reader(open(path)?);
+
+    let headers = if schema.has_headers {
+        let headers = reader.headers().map_err(|e| csv_error(path, 1, e))?.clone();
+        Some(headers)
+    } else {
+        None
+    };
+    let columns = CsvColumns::resolve(path, schema, headers.as_ref())?;
+
+    read_records(path, &mut reader, mode, |record| {
+        let event = columns.to_event(record, schema.timestamp_format.as_deref())?;
+        on_event(event);
+        Ok(1)
+    })
+}
+
+/// Reads the attributes of the entries of a csv file with a header.
+///
+/// `on_entry` returns whether the entry was kept.
+pub fn read_feature_csv<F>(
+    path: &Path,
+    schema: &FeatureSchema,
+    mode: ParseMode,
+    mut on_entry: F,
+) -> Result<FeatureLoadReport, DatasetError>
+where
+    F: FnMut(String, Features) -> bool,
+{
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(schema.delimiter)
+        .flexible(true)
+        .from_reader(open(path)?);
+
+    let headers = reader.headers().map_err(|e| csv_error(path, 1, e))?.clone();
+    let position = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h.trim() == name)
+            .ok_or_else(|| {
+                DatasetError::schema(path, format!("column `{}` is not in the header", name))
+            })
+    };
+    let columns = |fields: &[FeatureField]| {
+        fields
+            .iter()
+            .map(|field| position(&field.source))
+            .collect::<Result<Vec<_>, DatasetError>>()
+    };
+
+    let id = position(&schema.id)?;
+    let categorical = columns(&schema.categorical)?;
+    let numeric = columns(&schema.numeric)?;
+    let text = columns(&schema.text)?;
+
+    read_records(path, &mut reader, mode, |record| {
+        let field = |idx: usize| record.get(idx).map(str::trim).filter(|v| !v.is_empty());
+
+        let id = field(id).ok_or("missing id")?;
+
+        let mut features = Features::new();
+        for (attribute, &idx) in schema.categorical.iter().zip(&categorical) {
+            let values = field(idx)
+                .into_iter()
+                .flat_map(|v| v.split(schema.list_separator));
+            for value in values.map(str::trim).filter(|v| !v.is_empty()) {
+                features = features.with_categorical(&attribute.name, value);
+            }
+        }
+        for (attribute, &idx) in schema.numeric.iter().zip(&numeric) {
+            if let Some(value) = field(idx) {
+                let value = numeric_feature(&attribute.source, value)?;
+                features = features.with_numeric(&attribute.name, value);
+            }
+        }
+        for (attribute, &idx) in schema.text.iter().zip(&text) {
+            if let Some(value) = field(idx) {
+                features = features.with_text(&attribute.name, value);
+            }
+        }
+
+        Ok(on_entry(id.to_string(), features) as usize)
+    })
+    .map(FeatureLoadReport::from_records)
+}
+
+/// Reads the attributes of the entries of a jsonl file, one object per line.
+///
+/// `on_entry` returns whether the entry was kept.
+pub fn read_feature_jsonl<F>(
+    path: &Path,
+    schema: &FeatureSchema,
+    mode: ParseMode,
+    mut on_entry: F,
+) -> Result<FeatureLoadReport, DatasetError>
+where
+    F: FnMut(String, Features) -> bool,
+{
+    read_lines(path, mode, |line| {
+        let value: Value = serde_json::from_str(line).map_err(|e| e.to_string())?;
+        let id = value
+            .pointer(&pointer(schema.id.as_str()))
+            .and_then(json_id)
+            .ok_or_else(|| format!("missing id at `{}`", schema.id))?;
+
+        let mut features = Features::new();
+        for attribute in &schema.categorical {
+            let values = match value.pointer(&pointer(attribute.source.as_str())) {
+                Some(Value::Array(elements)) => elements.iter().filter_map(json_id).collect(),
+                Some(element) => json_id(element).into_iter().collect(),
+                None => vec![],
+            };
+            for v in values {
+                features = features.with_categorical(&attribute.name, v);
+            }
         }
-        on_event(Event::new(e.user_id.unwrap(), e.product_id.unwrap()));
+        for attribute in &schema.numeric {
+            let source = &attribute.source;
+            match value.pointer(&pointer(source.as_str())) {
+                Some(Value::Number(n)) => {
+                    let n = n.as_f64().ok_or_else(|| format!("invalid `{}`", source))?;
+                    features = features.with_numeric(&attribute.name, n);
+                }
+                Some(Value::String(s)) if !s.trim().is_empty() => {
+                    let n = numeric_feature(source, s.trim())?;
+                    features = features.with_numeric(&attribute.name, n);
+                }
+                Some(Value::Null | Value::String(_)) | None => {}
+                Some(other) => return Err(format!("invalid `{}`: {}", source, other)),
+            }
+        }
+        for attribute in &schema.text {
+            if let Some(Value::String(s)) = value.pointer(&pointer(attribute.source.as_str())) {
+                features = features.with_text(&attribute.name, s.trim());
+            }
+        }
+
+        Ok(on_entry(id, features) as usize)
+    })
+    .map(FeatureLoadReport::from_records)
+}
+
+fn numeric_feature(name: &str, value: &str) -> Result<f64, String> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite())
+        .ok_or_else(|| format!("invalid `{}` `{}`", name, value))
+}
+
+/// Calls `on_record` with every record of a csv reader, see [`read_lines`].
+fn read_records<R, F>(
+    path: &Path,
+    reader: &mut csv::Reader<R>,
+    mode: ParseMode,
+    mut on_record: F,
+) -> Result<LoadReport, DatasetError>
+where
+    R: io::Read,
+    F: FnMut(&StringRecord) -> Result<usize, String>,
+{
+    let mut report = LoadReport::default();
+    let mut record = StringRecord::new();
+    loop {
+        let line = reader.position().line();
+        match reader.read_record(&mut record) {
+            Ok(false) => break,
+            Ok(true) => {
+                report.records_read += 1;
+                match on_record(&record) {
+                    Ok(events) => report.events_loaded += events,
+                    Err(reason) => report.reject(mode, path, line, reason)?,
+                }
+            }
+            Err(e) if e.is_io_error() => return Err(csv_error(path, line, e)),
+            Err(e) => {
+                report.records_read += 1;
+                report.reject(mode, path, line, e.to_string())?;
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+fn csv_error(path: &Path, line: u64, e: csv::Error) -> DatasetError {
+    if !e.is_io_error() {
+        return DatasetError::parse(path, line, e.to_string());
+    }
+    match e.into_kind() {
+        csv::ErrorKind::Io(e) => DatasetError::io(path, e),
+        _ => unreachable!(),
     }
 }
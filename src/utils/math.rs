@@ -1,3 +1,6 @@
+// only used by the in-memory cosine similarity engine, which is disabled for now.
+#![allow(dead_code)]
+
 use std::collections::HashMap;
 
 pub fn cosine_similarity(a: &HashMap<String, u32>, b: &HashMap<String, u32>) -> f64 {
@@ -9,12 +12,7 @@ pub fn cosine_similarity(a: &HashMap<String, u32>, b: &HashMap<String, u32>) ->
 }
 
 pub fn norm(a: &HashMap<String, u32>) -> f64 {
-    let sum_of_squares: u64 = a
-        .values()
-        .into_iter()
-        .map(|&i| i as u64)
-        .map(|v| v * v)
-        .sum();
+    let sum_of_squares: u64 = a.values().map(|&i| i as u64).map(|v| v * v).sum();
     (sum_of_squares as f64).sqrt()
 }
 
@@ -3,15 +3,22 @@ use std::{
     fmt::{Debug, Display},
 };
 
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
 use crate::core::model::Event;
 
+// the hashmap based matrices are only used by the in-memory cosine similarity engine, which is disabled for now.
+#[allow(dead_code)]
 pub type UserItemMatrix = HashMap<String, HashMap<String, u32>>;
+#[allow(dead_code)]
 pub type ItemUserMatrix = HashMap<String, HashMap<String, u32>>;
 
+#[allow(dead_code)]
 pub fn print_hashmap<T: Display + Debug>(hm: HashMap<String, HashMap<String, T>>) {
     println!("{:?}", hm)
 }
 
+#[allow(dead_code)]
 pub fn construct_user_item_matrix(events: &Vec<Event>) -> UserItemMatrix {
     let mut user_item_matrix = UserItemMatrix::new();
 
@@ -28,6 +35,7 @@ pub fn construct_user_item_matrix(events: &Vec<Event>) -> UserItemMatrix {
     user_item_matrix
 }
 
+#[allow(dead_code)]
 pub fn construct_item_user_matrix(events: &Vec<Event>) -> ItemUserMatrix {
     let mut item_user_matrix = ItemUserMatrix::new();
 
@@ -48,6 +56,52 @@ pub fn approx_equal(a: f64, b: f64, epsilon: f64) -> bool {
     (a - b).abs() < epsilon
 }
 
+/// Parses a timestamp with the given `chrono` format, or by guessing among the common layouts.
+///
+/// Values without an offset are taken as UTC.
+pub fn parse_timestamp(value: &str, format: Option<&str>) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+
+    if let Some(format) = format {
+        return DateTime::parse_from_str(value, format)
+            .map(|t| t.with_timezone(&Utc))
+            .ok()
+            .or_else(|| {
+                NaiveDateTime::parse_from_str(value, format)
+                    .ok()
+                    .map(|t| Utc.from_utc_datetime(&t))
+            })
+            .or_else(|| {
+                NaiveDate::parse_from_str(value, format)
+                    .ok()
+                    .and_then(|d| d.and_hms_opt(0, 0, 0))
+                    .map(|t| Utc.from_utc_datetime(&t))
+            });
+    }
+
+    if let Ok(n) = value.parse::<i64>() {
+        // anything past the year 5138 in seconds is more likely to be milliseconds.
+        return if n.abs() >= 100_000_000_000 {
+            Utc.timestamp_millis_opt(n).single()
+        } else {
+            Utc.timestamp_opt(n, 0).single()
+        };
+    }
+    if let Ok(t) = DateTime::parse_from_rfc3339(value) {
+        return Some(t.with_timezone(&Utc));
+    }
+
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(value, f).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        })
+        .map(|t| Utc.from_utc_datetime(&t))
+}
+
 pub mod dataset;
 pub mod math;
 
@@ -76,6 +130,28 @@ mod util_tests {
         assert_eq!(1, user_item_matrix["user-2"]["product-1"]);
     }
 
+    #[test]
+    fn should_parse_timestamps() {
+        let expected = Utc.with_ymd_and_hms(2010, 12, 1, 8, 26, 0).unwrap();
+
+        assert_eq!(Some(expected), parse_timestamp("1291191960", None));
+        assert_eq!(Some(expected), parse_timestamp("1291191960000", None));
+        assert_eq!(
+            Some(expected),
+            parse_timestamp("2010-12-01T08:26:00Z", None)
+        );
+        assert_eq!(
+            Some(expected),
+            parse_timestamp("2010-12-01T10:26:00+02:00", None)
+        );
+        assert_eq!(Some(expected), parse_timestamp("2010-12-01 08:26:00", None));
+        assert_eq!(
+            Some(expected),
+            parse_timestamp("12/1/2010 8:26", Some("%m/%d/%Y %H:%M"))
+        );
+        assert_eq!(None, parse_timestamp("yesterday", None));
+    }
+
     #[test]
     fn cosine_similarity_should_work() {
         let a = HashMap::from([
@@ -1,42 +1,294 @@
-use std::{fmt::Error, time::Instant};
+use std::{env, fmt::Error, fs, time::Instant};
+
+use ndarray::{Array1, Array2};
 
 use rs_mender::{
-    core::{dataset::Dataset, similarity::SimilarityEngine},
+    core::{
+        dataset::{Aggregation, Dataset, DatasetBuilder, FeedbackKind},
+        error::RecommenderError,
+        model::{Event, EventType, RecommendationResponse},
+        similarity::SimilarityEngine,
+        split::SplitStrategy,
+        synthetic::SyntheticConfig,
+    },
     engine::matrix_factorization_engine::MatrixFactorizationEngine,
 };
 
 #[test]
 fn foo2() -> Result<(), Error> {
-    let dataset = Dataset::from_jsonl("./data/test_data.jsonl".to_string());
+    let synthetic = SyntheticConfig::new(200, 100)
+        .with_clusters(4)
+        .with_density(0.1)
+        .with_seed(1)
+        .generate()
+        .unwrap();
 
-    let mut engine = MatrixFactorizationEngine::new(dataset);
+    let mut engine = MatrixFactorizationEngine::new(synthetic.dataset);
 
-    engine.train();
+    let report = engine.train().unwrap();
+    let best_epoch = report.best_epoch.unwrap();
+    assert!(report.epoch_errors.len() > best_epoch);
+    assert!(report.epoch_errors[best_epoch] < report.epoch_errors[0]);
+    assert!(report.train_mpr.unwrap() < 0.5);
 
     let start = Instant::now();
     println!(
         "{:?}, time passed: {:?}ms",
-        engine.find_similar_by_user_id("0fb1e031d84a".to_string(), 10),
+        engine.find_similar_by_user_id("user-7".to_string(), 10),
         start.elapsed().as_millis()
     );
 
+    let response = engine
+        .find_similar_by_user_id("user-7".to_string(), 10)
+        .unwrap();
+    assert_eq!(10, response.len());
+    assert_eq!("matrix_factorization", response.engine());
+    assert_eq!(engine.model_version().unwrap(), response.model_version());
+    let ranked = response.recommendations();
+    assert!(ranked.windows(2).all(|w| w[0].score() >= w[1].score()));
+    assert_eq!(
+        vec![1, 2, 3],
+        ranked.iter().take(3).map(|r| r.rank()).collect::<Vec<_>>()
+    );
+
+    let json = serde_json::to_string(&response).unwrap();
+    let decoded = serde_json::from_str::<RecommendationResponse>(&json).unwrap();
+    assert_eq!(response.model_version(), decoded.model_version());
+    assert!(response.item_ids().eq(decoded.item_ids()));
+    assert!((ranked[0].score() - decoded.recommendations()[0].score()).abs() < 1e-12);
+
+    let similar = engine
+        .find_similar_by_target_id("item-3".to_string(), 5)
+        .unwrap();
+    assert_eq!(5, similar.len());
+    assert!(similar.item_ids().all(|id| id != "item-3"));
+    assert_eq!(
+        Some("similar to item-3"),
+        similar.recommendations()[0].reason()
+    );
+
+    let unknown = engine.find_similar_by_user_id("nobody".to_string(), 10);
+    assert!(matches!(unknown, Err(RecommenderError::UnknownUser(_))));
+
     Ok(())
 }
 
 #[test]
-fn foo() -> Result<(), Error> {
-    let dataset = Dataset::from_csv_example();
+fn should_rank_held_out_items_of_the_planted_clusters_high() -> Result<(), Error> {
+    let synthetic = SyntheticConfig::new(200, 100)
+        .with_clusters(4)
+        .with_density(0.1)
+        .with_seed(2)
+        .generate()
+        .unwrap();
 
-    let mut engine = MatrixFactorizationEngine::new(dataset);
+    let (train, test) = synthetic
+        .dataset
+        .split(&SplitStrategy::LeaveOneOut, 2)
+        .unwrap();
 
-    engine.train();
+    let mut engine = MatrixFactorizationEngine::new(train);
 
-    let start = Instant::now();
-    println!(
-        "{:?}, time passed: {:?}ms",
-        engine.find_similar_by_user_id("17850".to_string(), 10),
-        start.elapsed().as_millis()
+    engine.train().unwrap();
+
+    let mpr = engine.evaluate_mpr(&test).unwrap();
+    // ranking at random would give 0.5
+    assert!(mpr < 0.4, "mpr was {}", mpr);
+
+    // every interaction of the training dataset was seen, none is left to rank.
+    assert!(matches!(
+        engine.evaluate_mpr(engine.dataset()),
+        Err(RecommenderError::InvalidConfig(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn should_forget_a_user_of_a_trained_model() -> Result<(), Error> {
+    let synthetic = SyntheticConfig::new(40, 20)
+        .with_density(0.2)
+        .with_seed(3)
+        .generate()
+        .unwrap();
+
+    let model_dir = env::temp_dir().join("rs_mender_forget_model");
+    fs::create_dir_all(&model_dir).unwrap();
+    let mut engine = MatrixFactorizationEngine::new(synthetic.dataset).with_model_dir(&model_dir);
+
+    engine.train().unwrap();
+    engine.save().unwrap();
+
+    let u_path = engine.factors_path("u_matrix");
+    let v_path = engine.factors_path("v_matrix");
+    let user_bias_path = engine.factors_path("user_bias");
+    let item_bias_path = engine.factors_path("item_bias");
+    let mean_path = engine.factors_path("global_mean");
+    let read_factors =
+        |path| -> Array2<f64> { serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap() };
+    let read_bias =
+        |path| -> Array1<f64> { serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap() };
+    let saved = read_factors(&u_path);
+    let saved_items = read_factors(&v_path);
+    let saved_bias = read_bias(&user_bias_path);
+    let saved_item_bias = fs::read_to_string(&item_bias_path).unwrap();
+    let saved_mean = fs::read_to_string(&mean_path).unwrap();
+    let before = engine.predict_rating("user-8", "item-0").unwrap();
+    let record = engine.remove_user("user-7").unwrap().unwrap();
+
+    assert_eq!(7, record.former_idx);
+    assert_eq!(
+        vec![
+            "dataset".to_string(),
+            "u_matrix".to_string(),
+            "user_bias".to_string(),
+            u_path.display().to_string(),
+            user_bias_path.display().to_string()
+        ],
+        record.removed_from
+    );
+    // the persisted factors lost the row of the user, the following rows moved up.
+    let purged = read_factors(&u_path);
+    assert_eq!(saved.nrows() - 1, purged.nrows());
+    assert_eq!(saved.row(8), purged.row(7));
+    assert_eq!(saved_items, read_factors(&v_path));
+    let purged_bias = read_bias(&user_bias_path);
+    assert_eq!(saved_bias.len() - 1, purged_bias.len());
+    assert_eq!(saved_bias[8], purged_bias[7]);
+    assert_eq!(
+        saved_item_bias,
+        fs::read_to_string(&item_bias_path).unwrap()
     );
+    assert_eq!(saved_mean, fs::read_to_string(&mean_path).unwrap());
+    fs::remove_dir_all(&model_dir).unwrap();
+
+    assert!(matches!(
+        engine.predict_rating("user-7", "item-0"),
+        Err(RecommenderError::UnknownUser(id)) if id == "user-7"
+    ));
+    // the following users keep their factors under their new index.
+    assert_eq!(before, engine.predict_rating("user-8", "item-0").unwrap());
+    // factors and dataset still line up.
+    assert!(engine.calculate_mpr().unwrap() < 1.0);
+
+    Ok(())
+}
+
+#[test]
+fn should_predict_explicit_ratings_better_than_the_mean_rating() -> Result<(), Error> {
+    let mut builder = DatasetBuilder::new()
+        .with_aggregation(Aggregation::Latest)
+        .with_feedback(FeedbackKind::rating_scale(1.0, 5.0).unwrap());
+    for user in 0..100 {
+        for item in 0..50 {
+            if (user * 7 + item * 13) % 3 != 0 {
+                continue;
+            }
+            // generous users and well liked items rate higher.
+            let rating = 3.0 + (user % 5) as f64 * 0.5 - 1.0 + (item % 4) as f64 * 0.6 - 0.9;
+            builder.push(
+                Event::new(format!("user-{}", user), format!("item-{}", item))
+                    .with_event_type(EventType::Rating)
+                    .with_weight(rating.round().clamp(1.0, 5.0)),
+            );
+        }
+    }
+    let dataset = builder.build();
+
+    let (train, test) = dataset
+        .split(&SplitStrategy::Random { test_ratio: 0.2 }, 3)
+        .unwrap();
+
+    let mean_rating = train.cui.data().iter().sum::<f64>() / train.cui.nnz() as f64;
+    let mean_rmse = (test
+        .cui
+        .iter()
+        .map(|(r, _)| (r - mean_rating).powi(2))
+        .sum::<f64>()
+        / test.cui.nnz() as f64)
+        .sqrt();
+
+    let mut engine = MatrixFactorizationEngine::new(train);
+
+    engine.train().unwrap();
+
+    let errors = engine.evaluate_ratings(&test).unwrap();
+    assert_eq!(test.cui.nnz(), errors.count);
+    assert!(
+        errors.rmse < mean_rmse,
+        "rmse was {}, {} predicting the mean",
+        errors.rmse,
+        mean_rmse
+    );
+    assert!(errors.mae <= errors.rmse);
+
+    let empty = Vec::<Event>::new()
+        .into_iter()
+        .collect::<Dataset>()
+        .reindex(&test.user_idx, &test.item_idx);
+    assert!(matches!(
+        engine.evaluate_ratings(&empty),
+        Err(RecommenderError::InvalidConfig(_))
+    ));
+
+    let rating = engine.predict_rating("user-4", "item-3").unwrap();
+    assert!((1.0..=5.0).contains(&rating), "rating was {}", rating);
+    assert!(matches!(
+        engine.predict_rating("user-4", "unknown"),
+        Err(RecommenderError::UnknownItem(id)) if id == "unknown"
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn should_report_failures_of_an_untrained_engine_instead_of_panicking() -> Result<(), Error> {
+    let synthetic = SyntheticConfig::new(20, 10)
+        .with_density(0.3)
+        .with_seed(5)
+        .generate()
+        .unwrap();
+    let other = SyntheticConfig::new(5, 5).with_seed(5).generate().unwrap();
+
+    let engine = MatrixFactorizationEngine::new(synthetic.dataset);
+
+    assert!(matches!(
+        engine.find_similar_by_user_id("user-1".to_string(), 5),
+        Err(RecommenderError::NotTrained)
+    ));
+    assert!(matches!(
+        engine.find_similar_by_target_id("item-1".to_string(), 5),
+        Err(RecommenderError::NotTrained)
+    ));
+    assert!(matches!(
+        engine.calculate_mpr(),
+        Err(RecommenderError::NotTrained)
+    ));
+    assert!(matches!(
+        engine.evaluate_mpr(&other.dataset),
+        Err(RecommenderError::InvalidConfig(_))
+    ));
+    assert!(matches!(engine.save(), Err(RecommenderError::NotTrained)));
+
+    let mut empty = MatrixFactorizationEngine::new(Vec::<Event>::new().into_iter().collect());
+    assert!(matches!(
+        empty.train(),
+        Err(RecommenderError::InvalidConfig(_))
+    ));
+
+    // squared errors of such values overflow right away.
+    let huge = (0..10)
+        .map(|i| Event::new(format!("user-{}", i % 3), format!("item-{}", i)).with_weight(1e200))
+        .collect();
+    let mut diverging = MatrixFactorizationEngine::new(huge);
+    assert!(matches!(
+        diverging.train(),
+        Err(RecommenderError::NumericalDivergence { epoch: 0 })
+    ));
+    assert!(matches!(
+        diverging.predict_rating("user-0", "item-0"),
+        Err(RecommenderError::NotTrained)
+    ));
 
     Ok(())
 }